@@ -7,7 +7,7 @@ extern crate env_logger;
 
 use std::cell::Cell;
 use std::rc::Rc;
-use kamikaze_di::{Container, ContainerBuilder, Inject, InjectAsRc, Result, Injector};
+use kamikaze_di::{Container, ContainerBuilder, Inject, InjectAsRc, Injectable, Result, Injector};
 
 const TEXT_RESET: &str = "\x1b[1;0m";
 const TEXT_BOLD: &str = "\x1b[1;1m";
@@ -23,6 +23,8 @@ struct Config {
     normal_color: String,
 }
 
+impl Injectable for Config {}
+
 trait Voice {
     fn say(&self, line: &str) -> String;
 }
@@ -0,0 +1,628 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::{DeserializeOwned, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::{Map, Value};
+
+use crate::{Container, ContainerBuilder, Dependency, Inject, Result};
+
+/// Somewhere configuration is loaded from.
+///
+/// Sources are layered with [LayeredConfig](struct.LayeredConfig.html).
+pub trait ConfigSource {
+    /// Loads the configuration, as a tree of sections.
+    fn load(&self) -> Result<Value>;
+}
+
+/// A TOML or JSON file, picked by its extension.
+#[derive(Clone, Debug)]
+pub struct ConfigFile {
+    path: PathBuf,
+    required: bool,
+}
+
+impl ConfigFile {
+    /// A file that has to exist.
+    pub fn new<P: AsRef<Path>>(path: P) -> ConfigFile {
+        ConfigFile {
+            path: path.as_ref().to_path_buf(),
+            required: true,
+        }
+    }
+
+    /// Makes a missing file count as empty, for optional overrides.
+    pub fn optional(mut self) -> ConfigFile {
+        self.required = false;
+
+        self
+    }
+}
+
+impl ConfigSource for ConfigFile {
+    fn load(&self) -> Result<Value> {
+        if !self.required && !self.path.exists() {
+            return Ok(Value::Object(Map::new()));
+        }
+
+        let path = self.path.display();
+        let text = fs::read_to_string(&self.path)
+            .map_err(|error| format!("Could not read config file {}: {}", path, error))?;
+
+        let values = match self
+            .path
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("toml") => toml::from_str(&text).map_err(|error| error.to_string()),
+            Some("json") => serde_json::from_str(&text).map_err(|error| error.to_string()),
+            _ => Err("unknown format, use .toml or .json".to_string()),
+        };
+
+        values.map_err(|error| format!("Could not parse config file {}: {}", path, error).into())
+    }
+}
+
+/// Environment variables starting with a prefix.
+///
+/// `APP_DATABASE__URL` is read as `database.url` with the prefix `APP`:
+/// the prefix is cut off, sections are separated by double underscores,
+/// and names are lowercased. Values stay strings, they are read as
+/// numbers or booleans when a section has a number or boolean there.
+#[derive(Clone, Debug)]
+pub struct EnvVars {
+    prefix: String,
+    vars: Option<Vec<(String, String)>>,
+}
+
+impl EnvVars {
+    /// Variables starting with `prefix` and an underscore.
+    pub fn with_prefix<S: Into<String>>(prefix: S) -> EnvVars {
+        EnvVars {
+            prefix: format!("{}_", prefix.into()),
+            vars: None,
+        }
+    }
+
+    /// Reads vars instead of the environment of the process, for tests.
+    pub fn with_vars<I, K, V>(mut self, vars: I) -> EnvVars
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let vars = vars
+            .into_iter()
+            .map(|(name, value)| (name.into(), value.into()));
+        self.vars = Some(vars.collect());
+
+        self
+    }
+}
+
+impl ConfigSource for EnvVars {
+    fn load(&self) -> Result<Value> {
+        let vars = match &self.vars {
+            Some(vars) => vars.clone(),
+            None => env::vars().collect(),
+        };
+
+        let mut values = Map::new();
+
+        for (name, value) in vars {
+            if let Some(name) = name.strip_prefix(&self.prefix) {
+                let path: Vec<_> = name.split("__").map(str::to_lowercase).collect();
+
+                insert(&mut values, &path, Value::String(value));
+            }
+        }
+
+        Ok(Value::Object(values))
+    }
+}
+
+/// Values set in code, for defaults and tests.
+#[derive(Clone, Debug, Default)]
+pub struct InMemory {
+    values: Map<String, Value>,
+}
+
+impl InMemory {
+    /// Constructor.
+    pub fn new() -> InMemory {
+        Default::default()
+    }
+
+    /// Sets a value, by its dotted path, like `database.url`.
+    pub fn set<V: Into<Value>>(mut self, path: &str, value: V) -> InMemory {
+        let path: Vec<_> = path.split('.').map(str::to_string).collect();
+        insert(&mut self.values, &path, value.into());
+
+        self
+    }
+}
+
+impl ConfigSource for InMemory {
+    fn load(&self) -> Result<Value> {
+        Ok(Value::Object(self.values.clone()))
+    }
+}
+
+/// Configuration sources, merged in the order they were added.
+///
+/// Later sources override the values of earlier ones. Sections are
+/// merged key by key, everything else is replaced.
+///
+/// # Examples
+///
+/// ```
+/// # use kamikaze_di::{ConfigFile, EnvVars, InMemory, LayeredConfig};
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// let settings = LayeredConfig::new()
+///     .with_source(InMemory::new().set("server.port", 80).set("server.host", "localhost"))
+///     .with_source(ConfigFile::new("local.toml").optional())
+///     .with_source(EnvVars::with_prefix("MY_APP"))
+///     .with_source(InMemory::new().set("server.port", 8080))
+///     .load()?;
+///
+/// assert_eq!(8080, settings.get::<u16>("server.port")?);
+/// assert_eq!("localhost", settings.get::<String>("server.host")?);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct LayeredConfig {
+    sources: Vec<Box<dyn ConfigSource>>,
+}
+
+impl LayeredConfig {
+    /// Constructor.
+    pub fn new() -> LayeredConfig {
+        Default::default()
+    }
+
+    /// Adds a source, overriding the ones added before it.
+    pub fn with_source<S: ConfigSource + 'static>(mut self, source: S) -> LayeredConfig {
+        self.sources.push(Box::new(source));
+
+        self
+    }
+
+    /// Loads and merges all sources.
+    pub fn load(&self) -> Result<Settings> {
+        let mut values = Value::Object(Map::new());
+
+        for source in &self.sources {
+            merge(&mut values, source.load()?);
+        }
+
+        Ok(Settings { values })
+    }
+}
+
+impl fmt::Debug for LayeredConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LayeredConfig({} sources)", self.sources.len())
+    }
+}
+
+/// Loaded configuration, see [LayeredConfig](struct.LayeredConfig.html).
+#[derive(Clone, Debug)]
+pub struct Settings {
+    values: Value,
+}
+
+impl Settings {
+    /// Deserializes the value at a dotted path, like `database.pool`. The
+    /// empty path is the whole configuration.
+    ///
+    /// Strings are read as numbers or booleans where T has one, since
+    /// environment variables are always strings.
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let value = path
+            .split('.')
+            .filter(|name| !name.is_empty())
+            .try_fold(&self.values, |value, name| value.get(name))
+            .ok_or_else(|| format!("Config section not found: {}", path))?;
+
+        T::deserialize(Lenient(value.clone()))
+            .map_err(|error| format!("Could not read config section {}: {}", path, error).into())
+    }
+}
+
+/// A typed section of the configuration.
+///
+/// Sections implement [Inject](trait.Inject.html): once the Settings are
+/// registered, they are read from them when they are injected, into
+/// derived types too. They can also be registered on their own with
+/// [register_config()](struct.ContainerBuilder.html#method.register_config).
+///
+/// Since every section implements Inject, they can't implement it
+/// themselves.
+///
+/// # Examples
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use kamikaze_di::{ConfigSection, ContainerBuilder, InMemory, Injector, LayeredConfig};
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// #[derive(Clone, Deserialize)]
+/// struct DatabaseConfig {
+///     url: String,
+/// }
+///
+/// impl ConfigSection for DatabaseConfig {
+///     const SECTION: &'static str = "database";
+/// }
+///
+/// let settings = LayeredConfig::new()
+///     .with_source(InMemory::new().set("database.url", "mysql://localhost"))
+///     .load()?;
+///
+/// let mut builder = ContainerBuilder::new();
+/// builder.register(settings)?;
+///
+/// let container = builder.build();
+/// let database: DatabaseConfig = container.inject()?;
+///
+/// assert_eq!("mysql://localhost", database.url);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub trait ConfigSection: DeserializeOwned + Clone + 'static {
+    /// Dotted path of the section, empty for the whole configuration.
+    const SECTION: &'static str;
+}
+
+impl<T: ConfigSection> Inject for T {
+    fn resolve(container: &Container) -> Result<T> {
+        container.with(|settings: &Settings| settings.get::<T>(T::SECTION))?
+    }
+
+    fn dependencies() -> Vec<Dependency> {
+        vec![Dependency::of::<Settings>()]
+    }
+}
+
+impl ContainerBuilder {
+    /// Deserializes a section of the configuration and registers it.
+    ///
+    /// See [ConfigSection](trait.ConfigSection.html).
+    #[track_caller]
+    pub fn register_config<T: ConfigSection>(&mut self, settings: &Settings) -> Result<()> {
+        let section = settings.get::<T>(T::SECTION)?;
+
+        self.register::<T>(section)
+    }
+}
+
+impl Container {
+    /// Registers a section of the configuration on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_config()](struct.ContainerBuilder.html#method.register_config).
+    #[track_caller]
+    pub fn register_config<T: ConfigSection>(&self, settings: &Settings) -> Result<()> {
+        let section = settings.get::<T>(T::SECTION)?;
+
+        self.register::<T>(section)
+    }
+}
+
+/// Sets the value at path, creating sections as needed.
+fn insert(values: &mut Map<String, Value>, path: &[String], value: Value) {
+    match path {
+        [] => {}
+        [name] => {
+            values.insert(name.clone(), value);
+        }
+        [name, rest @ ..] => {
+            let section = values
+                .entry(name.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+
+            if !section.is_object() {
+                *section = Value::Object(Map::new());
+            }
+
+            if let Value::Object(section) = section {
+                insert(section, rest, value);
+            }
+        }
+    }
+}
+
+/// Merges layer into values, see LayeredConfig.
+fn merge(values: &mut Value, layer: Value) {
+    match (values, layer) {
+        (Value::Object(values), Value::Object(layer)) => {
+            for (name, value) in layer {
+                match values.get_mut(&name) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        values.insert(name, value);
+                    }
+                }
+            }
+        }
+        (values, layer) => *values = layer,
+    }
+}
+
+/// Deserializes a value, reading numbers and booleans out of strings
+/// where they are asked for, see Settings::get().
+struct Lenient(Value);
+
+/// Deserializes a scalar, parsing it first if it's a string.
+macro_rules! lenient_scalars {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> JsonResult<V::Value> {
+                match self.0 {
+                    Value::String(text) => match serde_json::from_str(&text) {
+                        Ok(parsed @ Value::Number(_)) | Ok(parsed @ Value::Bool(_)) => {
+                            parsed.$method(visitor)
+                        }
+                        _ => Value::String(text).$method(visitor),
+                    },
+                    value => value.$method(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Lenient {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> JsonResult<V::Value> {
+        match self.0 {
+            Value::Array(items) => visitor.visit_seq(LenientItems(items.into_iter())),
+            Value::Object(values) => visitor.visit_map(LenientValues {
+                values: values.into_iter(),
+                next: None,
+            }),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> JsonResult<V::Value> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(Lenient(value)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> JsonResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> JsonResult<V::Value> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    lenient_scalars! {
+        deserialize_bool
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct
+        map struct identifier ignored_any
+    }
+}
+
+type JsonResult<T> = std::result::Result<T, serde_json::Error>;
+
+/// Items of an array, see Lenient.
+struct LenientItems(std::vec::IntoIter<Value>);
+
+impl<'de> SeqAccess<'de> for LenientItems {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> JsonResult<Option<S::Value>> {
+        self.0
+            .next()
+            .map(|item| seed.deserialize(Lenient(item)))
+            .transpose()
+    }
+}
+
+/// Values of a section, see Lenient.
+struct LenientValues {
+    values: serde_json::map::IntoIter,
+    next: Option<Value>,
+}
+
+impl<'de> MapAccess<'de> for LenientValues {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> JsonResult<Option<S::Value>> {
+        match self.values.next() {
+            Some((name, value)) => {
+                self.next = Some(value);
+
+                seed.deserialize(Value::String(name)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> JsonResult<S::Value> {
+        let value = self.next.take().unwrap_or(Value::Null);
+
+        seed.deserialize(Lenient(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    use serde::Deserialize;
+
+    use super::{ConfigFile, ConfigSection, EnvVars, InMemory, LayeredConfig};
+    use crate::{Container, ContainerBuilder, Inject, Injector, Result};
+
+    #[derive(Clone, Debug, Deserialize, PartialEq)]
+    struct DatabaseConfig {
+        url: String,
+        pool: u32,
+        password: Option<String>,
+    }
+
+    impl ConfigSection for DatabaseConfig {
+        const SECTION: &'static str = "database";
+    }
+
+    #[derive(Clone)]
+    struct Repository {
+        database: DatabaseConfig,
+    }
+
+    /// A file in the temp directory, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &str) -> TempFile {
+            let name = format!("kamikaze_di_{}_{}", process::id(), name);
+            let path = env::temp_dir().join(name);
+            fs::write(&path, contents).unwrap();
+
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    impl Inject for Repository {
+        fn resolve(container: &Container) -> Result<Self> {
+            Ok(Repository {
+                database: container.inject()?,
+            })
+        }
+    }
+
+    #[test]
+    fn sources_are_layered() {
+        let file = TempFile::new(
+            "sources_are_layered.toml",
+            "[database]\nurl = \"mysql://file\"\npool = 4\n",
+        );
+
+        let settings = LayeredConfig::new()
+            .with_source(InMemory::new().set("database.url", "mysql://default"))
+            .with_source(ConfigFile::new(&file.0))
+            .with_source(ConfigFile::new("missing.json").optional())
+            .with_source(EnvVars::with_prefix("APP").with_vars(vec![
+                ("APP_DATABASE__POOL", "16"),
+                ("OTHER_DATABASE__POOL", "32"),
+            ]))
+            .load()
+            .unwrap();
+
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_config::<DatabaseConfig>(&settings)
+            .unwrap();
+        builder.register_automatic_factory::<Repository>().unwrap();
+
+        let container = builder.build();
+        let repository: Repository = container.inject().unwrap();
+
+        let expected = DatabaseConfig {
+            url: "mysql://file".to_string(),
+            pool: 16,
+            password: None,
+        };
+        assert_eq!(expected, repository.database);
+    }
+
+    #[test]
+    fn env_vars_stay_strings() {
+        let settings = LayeredConfig::new()
+            .with_source(EnvVars::with_prefix("APP").with_vars(vec![
+                ("APP_DATABASE__URL", "mysql://env"),
+                ("APP_DATABASE__POOL", "8"),
+                ("APP_DATABASE__PASSWORD", "12345"),
+                ("APP_DEBUG", "true"),
+            ]))
+            .load()
+            .unwrap();
+
+        let database: DatabaseConfig = settings.get("database").unwrap();
+
+        assert_eq!(8, database.pool);
+        assert_eq!(Some("12345".to_string()), database.password);
+        assert!(settings.get::<bool>("debug").unwrap());
+        assert_eq!("true", settings.get::<String>("debug").unwrap());
+    }
+
+    #[test]
+    fn sections_are_injected_from_registered_settings() {
+        let settings = LayeredConfig::new()
+            .with_source(
+                InMemory::new()
+                    .set("database.url", "mysql://memory")
+                    .set("database.pool", 2),
+            )
+            .load()
+            .unwrap();
+
+        let mut builder = ContainerBuilder::new();
+        builder.register(settings).unwrap();
+
+        let container = builder.build();
+        let database: DatabaseConfig = container.inject().unwrap();
+        let repository: Repository = container.inject().unwrap();
+
+        assert_eq!("mysql://memory", database.url);
+        assert_eq!(database, repository.database);
+        let empty = ContainerBuilder::new().build();
+        let missing: Result<DatabaseConfig> = empty.inject();
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn reports_missing_sections_and_files() {
+        let settings = LayeredConfig::new()
+            .with_source(InMemory::new().set("server.port", 80))
+            .load()
+            .unwrap();
+
+        assert!(settings.get::<DatabaseConfig>("database").is_err());
+        assert!(settings.get::<String>("server.port").is_err());
+        assert!(LayeredConfig::new()
+            .with_source(ConfigFile::new("missing.toml"))
+            .load()
+            .is_err());
+    }
+}
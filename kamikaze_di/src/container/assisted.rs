@@ -0,0 +1,181 @@
+use std::any::type_name;
+
+use super::graph::Dependency;
+use super::{Container, ResolverKind};
+use crate::{Error, Result};
+
+/// Creates Self from dependencies in the container and arguments
+/// only known at runtime.
+///
+/// Register it with
+/// [register_assisted_factory()](struct.ContainerBuilder.html#method.register_assisted_factory),
+/// then create items with
+/// [resolve_with()](struct.Container.html#method.resolve_with).
+///
+/// It can be derived, fields marked with `#[inject(arg)]` are the
+/// arguments, all other fields get injected. Args is the type of the
+/// argument field if there's only one, or a tuple of all argument
+/// types, in the order the fields are declared.
+///
+/// # Examples
+///
+/// ```
+/// use kamikaze_di::{AssistedFactory, Container, ContainerBuilder, Resolver, Result};
+///
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// struct UserSession { server: String, user_id: u64 }
+///
+/// impl AssistedFactory for UserSession {
+///     type Args = u64;
+///
+///     fn create(container: &Container, user_id: u64) -> Result<Self> {
+///         Ok(UserSession { server: container.resolve()?, user_id })
+///     }
+/// }
+///
+/// let mut builder = ContainerBuilder::new();
+/// builder.register::<String>("localhost".to_string())?;
+/// builder.register_assisted_factory::<UserSession>()?;
+///
+/// let container = builder.build();
+///
+/// let session: UserSession = container.resolve_with(42u64)?;
+///
+/// assert_eq!("localhost", session.server);
+/// assert_eq!(42, session.user_id);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub trait AssistedFactory: Sized + 'static {
+    /// Arguments given to [resolve_with()](struct.Container.html#method.resolve_with).
+    type Args: 'static;
+
+    /// Creates Self, using the container for everything that's not an argument.
+    fn create(container: &Container, args: Self::Args) -> Result<Self>;
+
+    /// Lists the types needed to create Self, arguments excluded.
+    ///
+    /// See [Inject::dependencies()](trait.Inject.html#method.dependencies).
+    fn dependencies() -> Vec<Dependency> {
+        vec![]
+    }
+}
+
+/// Factories taking arguments are stored under this type.
+///
+/// Shows up as "fn(Args) -> T" in registrations and the dependency graph.
+pub(super) type WithArgs<Args, T> = fn(Args) -> T;
+
+/// Factories called by resolve_with().
+pub(super) type FactoryWith<Args, T> = dyn FnMut(&Container, Args) -> Result<T>;
+
+impl Container {
+    /// Creates T with a factory that takes arguments.
+    ///
+    /// The factory has to be registered with
+    /// [register_factory_with()](struct.ContainerBuilder.html#method.register_factory_with)
+    /// or [register_assisted_factory()](struct.ContainerBuilder.html#method.register_assisted_factory),
+    /// for exactly the same Args.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{Container, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let container = Container::new();
+    /// container.register::<u32>(40)?;
+    /// container.register_factory_with::<(u32, u32), u64, _>(|container, (a, b)| {
+    ///     let base: u32 = container.resolve().unwrap();
+    ///     u64::from(base + a + b)
+    /// })?;
+    ///
+    /// assert_eq!(42, container.resolve_with::<u64, _>((1u32, 1u32))?);
+    /// assert!(container.resolve_with::<u64, _>(1u32).is_err());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_with<T: 'static, Args: 'static>(&self, args: Args) -> Result<T> {
+        self.tracked::<WithArgs<Args, T>, _, _>(|kind| match kind {
+            Some(ResolverKind::Factory) => self.call_factory_with::<Args, T>(args),
+            _ => Err(Error::not_registered(format!(
+                "No factory taking {} registered for {}",
+                type_name::<Args>(),
+                type_name::<T>()
+            ))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AssistedFactory;
+    use crate::{Container, ContainerBuilder, Resolver, Result};
+
+    #[derive(Debug)]
+    struct Session {
+        server: String,
+        user_id: u64,
+    }
+
+    impl AssistedFactory for Session {
+        type Args = u64;
+
+        fn create(container: &Container, user_id: u64) -> Result<Self> {
+            Ok(Session {
+                server: container.resolve()?,
+                user_id,
+            })
+        }
+    }
+
+    #[test]
+    fn assisted_factories_get_arguments() {
+        let mut builder = ContainerBuilder::new();
+        builder.register::<String>("localhost".to_string()).unwrap();
+        builder.register_assisted_factory::<Session>().unwrap();
+
+        let container = builder.build();
+
+        let first: Session = container.resolve_with(1u64).unwrap();
+        let second: Session = container.resolve_with(2u64).unwrap();
+
+        assert_eq!("localhost", first.server);
+        assert_eq!(1, first.user_id);
+        assert_eq!(2, second.user_id);
+    }
+
+    #[test]
+    fn assisted_factories_return_errors() {
+        let mut builder = ContainerBuilder::new();
+        builder.register_assisted_factory::<Session>().unwrap();
+
+        let container = builder.build();
+
+        let error = container.resolve_with::<Session, _>(1u64).unwrap_err();
+
+        assert!(error.is_not_registered());
+        assert_eq!(
+            "Type not registered: alloc::string::String",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn factories_with_arguments_are_not_resolved_directly() {
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_factory_with::<u64, String, _>(|_, id| id.to_string())
+            .unwrap();
+
+        let container = builder.build();
+
+        assert!(container.resolve::<String>().is_err());
+        assert!(container.resolve_with::<String, _>(1u32).is_err());
+        assert_eq!("1", container.resolve_with::<String, _>(1u64).unwrap());
+    }
+}
@@ -0,0 +1,240 @@
+use std::any::{type_name, TypeId};
+use std::fmt;
+use std::panic::Location;
+use std::rc::Rc;
+
+use super::builder::ContainerBuilder;
+use super::injector::Inject;
+use super::Container;
+use crate::Result;
+
+/// Builds any number of independent containers from one definition.
+///
+/// Blueprints take `Fn` factories and builders, and `Clone` values, so
+/// registering them can be repeated. Registrations are shared between
+/// clones of a blueprint and the containers it builds, building a
+/// container only creates new entries.
+///
+/// Every container gets its own items: builders are called once per
+/// container, values are cloned into each container. Use
+/// [register_shared()](struct.Blueprint.html#method.register_shared)
+/// to share one item between all of them.
+///
+/// # Examples
+///
+/// ```
+/// # use std::cell::Cell;
+/// # use kamikaze_di::{Blueprint, Resolver};
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// let mut blueprint = Blueprint::new();
+/// blueprint.register::<u32>(40)?;
+/// blueprint.register_builder::<Cell<u32>, _>(|container| {
+///     Cell::new(container.resolve::<u32>().unwrap() + 2)
+/// })?;
+///
+/// let first = blueprint.build();
+/// let second = blueprint.build();
+///
+/// first.resolve_ref::<Cell<u32>>()?.set(0);
+///
+/// assert_eq!(0, first.resolve_ref::<Cell<u32>>()?.get());
+/// assert_eq!(42, second.resolve_ref::<Cell<u32>>()?.get());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Blueprint {
+    recipes: Rc<Vec<Recipe>>,
+}
+
+/// Registers one type in a builder.
+type Register = dyn Fn(&mut ContainerBuilder) -> Result<()>;
+
+#[derive(Clone)]
+struct Recipe {
+    type_id: TypeId,
+    type_name: &'static str,
+    registered_at: &'static Location<'static>,
+    register: Rc<Register>,
+}
+
+impl Blueprint {
+    /// Constructor.
+    pub fn new() -> Blueprint {
+        Default::default()
+    }
+
+    /// Registers a value, each container gets a clone of it.
+    ///
+    /// See [ContainerBuilder::register()](struct.ContainerBuilder.html#method.register).
+    #[track_caller]
+    pub fn register<T: Clone + 'static>(&mut self, item: T) -> Result<()> {
+        self.add::<T, _>(move |builder| builder.register::<T>(item.clone()))
+    }
+
+    /// Registers an item shared by all containers, resolved as Rc<T>.
+    ///
+    /// See [ContainerBuilder::register_shared()](struct.ContainerBuilder.html#method.register_shared).
+    #[track_caller]
+    pub fn register_shared<T: 'static>(&mut self, item: T) -> Result<()> {
+        let item = Rc::new(item);
+
+        self.add::<Rc<T>, _>(move |builder| builder.register::<Rc<T>>(item.clone()))
+    }
+
+    /// Registers a factory, shared by all containers.
+    ///
+    /// See [ContainerBuilder::register_factory()](struct.ContainerBuilder.html#method.register_factory).
+    #[track_caller]
+    pub fn register_factory<T, F>(&mut self, factory: F) -> Result<()>
+    where
+        F: Fn(&Container) -> T + 'static,
+        T: 'static,
+    {
+        let factory = Rc::new(factory);
+
+        self.add::<T, _>(move |builder| {
+            let factory = factory.clone();
+
+            builder.register_factory::<T, _>(move |container| factory(container))
+        })
+    }
+
+    /// Registers T to be created by its [Inject](trait.Inject.html)
+    /// implementation, every time it's resolved.
+    ///
+    /// See [ContainerBuilder::register_automatic_factory()](struct.ContainerBuilder.html#method.register_automatic_factory).
+    #[track_caller]
+    pub fn register_automatic_factory<T: Inject + 'static>(&mut self) -> Result<()> {
+        self.add::<T, _>(|builder| builder.register_automatic_factory::<T>())
+    }
+
+    /// Registers a builder, called once per container.
+    ///
+    /// See [ContainerBuilder::register_builder()](struct.ContainerBuilder.html#method.register_builder).
+    #[track_caller]
+    pub fn register_builder<T, B>(&mut self, builder: B) -> Result<()>
+    where
+        B: Fn(&Container) -> T + 'static,
+        T: 'static,
+    {
+        let builder = Rc::new(builder);
+
+        self.add::<T, _>(move |container_builder| {
+            let builder = builder.clone();
+
+            container_builder.register_builder::<T, _>(move |container| builder(container))
+        })
+    }
+
+    /// Returns true if a dependency is registered.
+    pub fn has<T: 'static>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+
+        self.recipes.iter().any(|recipe| recipe.type_id == type_id)
+    }
+
+    /// Creates a builder with everything registered in the blueprint.
+    ///
+    /// Use it to add more registrations, observers or profiles to a
+    /// single container.
+    pub fn builder(&self) -> ContainerBuilder {
+        let mut builder = ContainerBuilder::new();
+
+        for recipe in self.recipes.iter() {
+            (recipe.register)(&mut builder).expect("blueprint registrations are unique");
+            builder.registered_at(recipe.type_id, recipe.registered_at);
+        }
+
+        builder
+    }
+
+    /// Creates a new container.
+    pub fn build(&self) -> Container {
+        self.builder().build()
+    }
+
+    #[track_caller]
+    fn add<T, F>(&mut self, register: F) -> Result<()>
+    where
+        T: 'static,
+        F: Fn(&mut ContainerBuilder) -> Result<()> + 'static,
+    {
+        if self.has::<T>() {
+            return Err(format!("Blueprint already has {}", type_name::<T>()).into());
+        }
+
+        Rc::make_mut(&mut self.recipes).push(Recipe {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            registered_at: Location::caller(),
+            register: Rc::new(register),
+        });
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Blueprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.recipes.iter().map(|recipe| recipe.type_name))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::Blueprint;
+    use crate::{Resolver, ResolverKind};
+
+    #[test]
+    fn containers_are_independent() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+
+        let mut blueprint = Blueprint::new();
+        blueprint.register::<u32>(1).unwrap();
+        blueprint.register_shared::<&str>("shared").unwrap();
+        blueprint
+            .register_builder::<u64, _>(move |_| {
+                counter.set(counter.get() + 1);
+                counter.get()
+            })
+            .unwrap();
+
+        let first = blueprint.build();
+        let second = blueprint.clone().build();
+
+        assert_eq!(1, first.resolve::<u64>().unwrap());
+        assert_eq!(1, first.resolve::<u64>().unwrap());
+        assert_eq!(2, second.resolve::<u64>().unwrap());
+
+        second.replace::<u32>(2).unwrap();
+        assert_eq!(1, first.resolve::<u32>().unwrap());
+
+        assert!(Rc::ptr_eq(
+            &first.resolve::<Rc<&str>>().unwrap(),
+            &second.resolve::<Rc<&str>>().unwrap()
+        ));
+    }
+
+    #[test]
+    fn containers_know_where_things_were_registered() {
+        let mut blueprint = Blueprint::new();
+        blueprint.register_factory::<u32, _>(|_| 42).unwrap();
+
+        assert!(blueprint.register::<u32>(1).is_err());
+
+        let registration = blueprint.build().registrations()[0];
+
+        assert_eq!(ResolverKind::Factory, registration.kind());
+        assert_eq!(file!(), registration.registered_at().unwrap().file());
+    }
+}
@@ -1,12 +1,27 @@
-use std::any::{Any, TypeId};
+use std::any::{type_name, TypeId};
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::hash::Hash;
+use std::panic::Location;
+use std::rc::Rc;
+use std::time::Duration;
 
+use super::assisted::{AssistedFactory, WithArgs};
 use super::cycle::CycleStopper;
-use super::injector::Inject;
-use crate::Result;
+use super::dynamic::name_entry;
+use super::graph::DependencyRecorder;
+use super::injector::{Inject, Injectable};
+use super::keyed::KeyedBy;
+use super::merge::{Conflict, ConflictPolicy};
+use super::module::Module;
+use super::observer::{Observers, ResolutionObserver};
+use super::per_thread::PerThread;
+use super::pool::Pool;
+use super::profile::SkippedRegistration;
+use crate::{Error, Result};
 
-use super::{Container, Resolver};
+use super::registration::Registration;
+use super::{Container, Entry, Resolver};
 
 /// Dependency container builder.
 ///
@@ -72,24 +87,347 @@ use super::{Container, Resolver};
 /// ```
 #[derive(Default, Debug)]
 pub struct ContainerBuilder {
-    resolvers: HashMap<TypeId, Resolver>,
+    entries: HashMap<TypeId, Entry>,
+    observers: Observers,
+    dependencies: DependencyRecorder,
+    /// Types resolved by build_eager(), besides eager registrations.
+    roots: Vec<(TypeId, (&'static str, Initializer))>,
+    /// Names of installed modules, in the order they were installed.
+    modules: Vec<&'static str>,
+    /// Names of the modules being installed, innermost last.
+    installing: Vec<&'static str>,
+    profiles: Vec<String>,
+    skipped: Vec<SkippedRegistration>,
 }
 
+/// Builds something inside the container, see build_eager().
+type Initializer = fn(&Container) -> Result<()>;
+
 impl ContainerBuilder {
     /// Constructor.
     pub fn new() -> ContainerBuilder {
         Default::default()
     }
 
+    /// Activates profiles, for use with
+    /// [when_profile()](struct.ContainerBuilder.html#method.when_profile).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::ContainerBuilder;
+    /// #
+    /// let builder = ContainerBuilder::new().with_profiles(vec!["test", "eu"]);
+    ///
+    /// assert!(builder.is_profile_active("test"));
+    /// assert!(!builder.is_profile_active("prod"));
+    /// ```
+    pub fn with_profiles<I>(mut self, profiles: I) -> ContainerBuilder
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.profiles.extend(profiles.into_iter().map(Into::into));
+
+        self
+    }
+
+    /// Returns true if the profile was activated with
+    /// [with_profiles()](struct.ContainerBuilder.html#method.with_profiles).
+    pub fn is_profile_active(&self, profile: &str) -> bool {
+        self.profiles.iter().any(|active| active == profile)
+    }
+
+    /// Makes the registrations done by register() only if profile is active.
+    ///
+    /// register() is not called for inactive profiles, the skip is listed by
+    /// [skipped()](struct.ContainerBuilder.html#method.skipped).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut builder = ContainerBuilder::new().with_profiles(vec!["test"]);
+    /// builder.when_profile("test", |builder| builder.register::<&str>("in memory"))?;
+    /// builder.when_profile("prod", |builder| builder.register::<&str>("mysql"))?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// assert_eq!("in memory", container.resolve::<&str>()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn when_profile<F>(&mut self, profile: &str, register: F) -> Result<()>
+    where
+        F: FnOnce(&mut ContainerBuilder) -> Result<()>,
+    {
+        if self.is_profile_active(profile) {
+            return register(self);
+        }
+
+        self.skip(format!("profile {} is not active", profile));
+
+        Ok(())
+    }
+
+    /// Makes the registrations done by register() only if condition()
+    /// returns true.
+    ///
+    /// Works like [when_profile()](struct.ContainerBuilder.html#method.when_profile),
+    /// description is used as the reason for skipping.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.when(
+    ///     "DATABASE_URL is set",
+    ///     |_| std::env::var("DATABASE_URL").is_ok(),
+    ///     |builder| builder.register::<&str>("mysql"),
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn when<C, F>(&mut self, description: &str, condition: C, register: F) -> Result<()>
+    where
+        C: FnOnce(&ContainerBuilder) -> bool,
+        F: FnOnce(&mut ContainerBuilder) -> Result<()>,
+    {
+        if condition(self) {
+            return register(self);
+        }
+
+        self.skip(format!("condition {} does not hold", description));
+
+        Ok(())
+    }
+
+    /// Lists the registrations skipped by
+    /// [when_profile()](struct.ContainerBuilder.html#method.when_profile) and
+    /// [when()](struct.ContainerBuilder.html#method.when), in the order
+    /// they were skipped.
+    pub fn skipped(&self) -> &[SkippedRegistration] {
+        &self.skipped
+    }
+
+    /// Records registrations that were skipped, without running them.
+    #[track_caller]
+    fn skip(&mut self, reason: String) {
+        let module = self.installing.last().copied();
+
+        self.skipped
+            .push(SkippedRegistration::new(reason, Location::caller(), module));
+    }
+
     /// Creates a Container from the builder.
     pub fn build(self) -> Container {
-        debug!("builder consumed");
         Container {
-            resolvers: RefCell::new(self.resolvers),
+            entries: RefCell::new(self.entries),
             cycle_stopper: CycleStopper::default(),
+            observers: self.observers,
+            dependencies: self.dependencies,
+            lazy: Default::default(),
+            skipped: self.skipped,
+            graph_items: Default::default(),
+            weak: Default::default(),
+        }
+    }
+
+    /// Creates a Container and builds everything marked to be built up front.
+    ///
+    /// Registrations marked with
+    /// [eager()](struct.ContainerBuilder.html#method.eager) are built,
+    /// sorted by type name, then the roots declared with
+    /// [eager_root()](struct.ContainerBuilder.html#method.eager_root) are
+    /// resolved, in the order they were declared. Types marked or declared
+    /// as roots go before those that declare a dependency on them, see
+    /// [Inject::dependencies()](trait.Inject.html#method.dependencies),
+    /// so the failure of a dependency is listed before those it causes.
+    /// Other builders are left alone, unless something needs them.
+    ///
+    /// Resolving continues after a failure, the error lists every type
+    /// that could not be built.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{Container, ContainerBuilder, Inject, Resolver, Result};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// #[derive(Clone)]
+    /// struct Broken;
+    /// impl Inject for Broken {
+    ///     fn resolve(_: &Container) -> Result<Self> {
+    ///         Err("no can do".into())
+    ///     }
+    /// }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_builder::<i32, _>(|_| 42)?;
+    /// builder.eager::<i32>()?;
+    /// builder.eager_root::<Broken>();
+    ///
+    /// let error = builder.build_eager().unwrap_err();
+    /// assert!(String::from(error).contains("no can do"));
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn build_eager(mut self) -> Result<Container> {
+        let mut initializers: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.eager)
+            .filter_map(|(type_id, entry)| {
+                entry
+                    .initialize
+                    .map(|initialize| (*type_id, (entry.type_name, initialize)))
+            })
+            .collect();
+        initializers.sort_by_key(|(_, (type_name, _))| *type_name);
+        initializers.append(&mut self.roots);
+        let initializers = self.dependencies.dependencies_first(initializers);
+
+        let container = self.build();
+
+        let failures: Vec<String> = initializers
+            .iter()
+            .filter_map(|(_, (type_name, initialize))| {
+                initialize(&container)
+                    .err()
+                    .map(|error| format!("{}: {}", type_name, error))
+            })
+            .collect();
+
+        if !failures.is_empty() {
+            return Err(format!("Could not build container:\n{}", failures.join("\n")).into());
+        }
+
+        Ok(container)
+    }
+
+    /// Marks the registration of T to be built by
+    /// [build_eager()](struct.ContainerBuilder.html#method.build_eager).
+    ///
+    /// Only builders can be built up front, items registered as they are
+    /// already are, marking them is an error. Types that are not registered can be declared as roots
+    /// with [eager_root()](struct.ContainerBuilder.html#method.eager_root).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::cell::Cell;
+    /// # use std::rc::Rc;
+    /// # use kamikaze_di::ContainerBuilder;
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let built = Rc::new(Cell::new(0));
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// let counter = built.clone();
+    /// builder.register_builder::<i32, _>(move |_| { counter.set(counter.get() + 1); 42 })?;
+    /// let counter = built.clone();
+    /// builder.register_builder::<i64, _>(move |_| { counter.set(counter.get() + 1); 42 })?;
+    /// builder.eager::<i32>()?;
+    ///
+    /// let _container = builder.build_eager()?;
+    /// assert_eq!(1, built.get());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn eager<T: 'static>(&mut self) -> Result<()> {
+        match self.entries.get_mut(&TypeId::of::<T>()) {
+            Some(entry) if entry.initialize.is_some() => {
+                entry.eager = true;
+
+                Ok(())
+            }
+            Some(entry) if matches!(entry.resolver, Resolver::Shared(_)) => Err(format!(
+                "Type {} is registered as an item, it's already built",
+                type_name::<T>()
+            )
+            .into()),
+            Some(_) => Err(format!(
+                "Type {} is not registered as a builder, it can't be built eagerly",
+                type_name::<T>()
+            )
+            .into()),
+            None => Err(Error::not_registered(format!(
+                "Type not registered: {}",
+                type_name::<T>()
+            ))),
         }
     }
 
+    /// Declares a type to be resolved by
+    /// [build_eager()](struct.ContainerBuilder.html#method.build_eager).
+    ///
+    /// Use this for types resolved through [Inject](trait.Inject.html) or
+    /// [InjectAsRc](trait.InjectAsRc.html), like the entry points of an
+    /// application, to find out early if they can't be built.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{Container, ContainerBuilder, Inject, Resolver, Result};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// #[derive(Clone)]
+    /// struct Service;
+    /// impl Inject for Service {
+    ///     fn resolve(_: &Container) -> Result<Self> {
+    ///         Ok(Service)
+    ///     }
+    /// }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.eager_root::<Service>();
+    ///
+    /// let container = builder.build_eager()?;
+    /// assert!(container.has::<Service>());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn eager_root<T: Injectable>(&mut self) {
+        self.dependencies.declare::<T>(&T::dependencies());
+        self.roots.push((
+            TypeId::of::<T>(),
+            (type_name::<T>(), initialize_injected::<T>),
+        ));
+    }
+
+    /// Adds an observer that gets notified about every resolution.
+    ///
+    /// See [ResolutionObserver](trait.ResolutionObserver.html) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, ResolutionEvent};
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.add_observer(|event: &ResolutionEvent| println!("{:?}", event));
+    /// ```
+    pub fn add_observer<O: ResolutionObserver + 'static>(&mut self, observer: O) {
+        self.observers.add(Rc::new(observer));
+    }
+
     /// Registeres a dependency directly.
     ///
     /// # Examples
@@ -102,13 +440,9 @@ impl ContainerBuilder {
     ///
     /// assert!(result.is_ok());
     /// ```
+    #[track_caller]
     pub fn register<T: 'static>(&mut self, item: T) -> Result<()> {
-        debug!("registering type");
-
-        // shared resolvers hold Box<Any>
-        let resolver = Resolver::Shared(Box::new(item));
-
-        self.insert::<T>(resolver)
+        self.insert::<T>(Entry::registered::<T>(Resolver::shared(item)))
     }
 
     /// Registers a factory.
@@ -144,21 +478,13 @@ impl ContainerBuilder {
     /// # Ok(())
     /// # }
     /// ```
+    #[track_caller]
     pub fn register_factory<T, F>(&mut self, factory: F) -> Result<()>
     where
-        F: (FnMut(&Container) -> T) + 'static,
+        F: (FnMut(&Container) -> T) + Clone + 'static,
         T: 'static,
     {
-        debug!("registering factory");
-
-        // We use double boxes so we can downcast to the inner box type.
-        // you can only downcast to Sized types, that's why we need an inner box
-        // see call_factory() for use.
-        let boxed: Box<dyn (FnMut(&Container) -> T) + 'static> = Box::new(factory);
-        let boxed: Box<dyn Any> = Box::new(boxed);
-        let resolver = Resolver::Factory(RefCell::new(boxed));
-
-        self.insert::<T>(resolver)
+        self.insert::<T>(Entry::factory::<T, F>(factory))
     }
 
     /// Every time a dependency is resolved, a new item will be created.
@@ -190,9 +516,255 @@ impl ContainerBuilder {
     /// # Ok(())
     /// # }
     /// ```
+    #[track_caller]
     pub fn register_automatic_factory<T: Inject + 'static>(&mut self) -> Result<()> {
-        debug!("registering auto factory");
-        self.register_factory(auto_factory::<T>)
+        self.register_factory(auto_factory::<T>)?;
+        self.dependencies.declare::<T>(&T::dependencies());
+
+        Ok(())
+    }
+
+    /// Registers a factory called once per outermost resolution.
+    ///
+    /// Everything built while resolving one type from outside the
+    /// container shares the same item, like a unit of work shared by all
+    /// repositories of a request handler. The item is dropped by the
+    /// container once that resolution finishes, the next one creates a
+    /// new item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::rc::Rc;
+    /// # use kamikaze_di::{Container, ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// struct UnitOfWork;
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_per_graph::<Rc<UnitOfWork>, _>(|_| Rc::new(UnitOfWork))?;
+    /// builder.register_factory::<(Rc<UnitOfWork>, Rc<UnitOfWork>), _>(|container| {
+    ///     (container.resolve().unwrap(), container.resolve().unwrap())
+    /// })?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let (users, orders) = container.resolve::<(Rc<UnitOfWork>, Rc<UnitOfWork>)>()?;
+    /// let (next_users, _) = container.resolve::<(Rc<UnitOfWork>, Rc<UnitOfWork>)>()?;
+    ///
+    /// assert!(Rc::ptr_eq(&users, &orders));
+    /// assert!(!Rc::ptr_eq(&users, &next_users));
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_per_graph<T, F>(&mut self, factory: F) -> Result<()>
+    where
+        F: (FnMut(&Container) -> T) + Clone + 'static,
+        T: 'static,
+    {
+        self.insert::<T>(Entry::per_graph::<T, F>(factory))
+    }
+
+    /// Registers a [Pool](struct.Pool.html) of size objects, resolved as Pool<T>.
+    ///
+    /// The objects are created with factory the first time the pool is
+    /// resolved. Check them out with
+    /// [Container::checkout()](struct.Container.html#method.checkout) or
+    /// [Container::try_checkout()](struct.Container.html#method.try_checkout),
+    /// or inject the Pool<T>.
+    ///
+    /// Pools can't be empty, a size of 0 is an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::ContainerBuilder;
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_pool::<Vec<u8>, _>(4, |_| Vec::with_capacity(1024))?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let mut buffer = container.checkout::<Vec<u8>>()?;
+    /// buffer.extend_from_slice(b"hello");
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_pool<T, F>(&mut self, size: usize, factory: F) -> Result<()>
+    where
+        F: (FnMut(&Container) -> T) + 'static,
+        T: Send + 'static,
+    {
+        self.insert::<Pool<T>>(Entry::pool(size, factory, None)?)
+    }
+
+    /// Registers a [Pool](struct.Pool.html), like
+    /// [register_pool()](struct.ContainerBuilder.html#method.register_pool),
+    /// where objects are reset before going back into the pool.
+    #[track_caller]
+    pub fn register_pool_with_reset<T, F, R>(
+        &mut self,
+        size: usize,
+        factory: F,
+        reset: R,
+    ) -> Result<()>
+    where
+        F: (FnMut(&Container) -> T) + 'static,
+        R: Fn(&mut T) + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        self.insert::<Pool<T>>(Entry::pool(size, factory, Some(Box::new(reset)))?)
+    }
+
+    /// Registers a factory for one item per OS thread, resolved as
+    /// [PerThread<T>](struct.PerThread.html).
+    ///
+    /// See PerThread for how items are built and how they relate to the container.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::cell::RefCell;
+    /// # use kamikaze_di::{ContainerBuilder, PerThread, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_per_thread::<RefCell<String>, _>(|| RefCell::new(String::new()))?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let buffer: PerThread<RefCell<String>> = container.resolve()?;
+    /// buffer.get().borrow_mut().push_str("hello");
+    ///
+    /// assert_eq!("hello", *buffer.get().borrow());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_per_thread<T, F>(&mut self, factory: F) -> Result<()>
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+        T: 'static,
+    {
+        let resolver = Resolver::shared(PerThread::new(factory));
+
+        self.insert::<PerThread<T>>(Entry::per_thread::<T>(resolver))
+    }
+
+    /// Registers a factory that takes arguments.
+    ///
+    /// Use this for types that need both dependencies from the container
+    /// and values only known at runtime. Items are created with
+    /// [Container::resolve_with()](struct.Container.html#method.resolve_with),
+    /// where Args has to match exactly. Use tuples for multiple arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// struct UserSession { server: String, user_id: u64 }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register::<String>("localhost".to_string())?;
+    /// builder.register_factory_with::<u64, UserSession, _>(|container, user_id| {
+    ///     UserSession {
+    ///         server: container.resolve().unwrap(),
+    ///         user_id,
+    ///     }
+    /// })?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let session: UserSession = container.resolve_with(42u64)?;
+    ///
+    /// assert_eq!("localhost", session.server);
+    /// assert_eq!(42, session.user_id);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_factory_with<Args, T, F>(&mut self, mut factory: F) -> Result<()>
+    where
+        F: (FnMut(&Container, Args) -> T) + Clone + 'static,
+        Args: 'static,
+        T: 'static,
+    {
+        let factory = move |container: &Container, args| Ok(factory(container, args));
+
+        self.insert::<WithArgs<Args, T>>(Entry::factory_with(factory))
+    }
+
+    /// Registers a keyed builder.
+    ///
+    /// The builder is called the first time an item is resolved for a
+    /// key, with [Container::resolve_keyed()](struct.Container.html#method.resolve_keyed).
+    /// After that, the item is cloned for that key, like for
+    /// [register_builder()](struct.ContainerBuilder.html#method.register_builder).
+    ///
+    /// Built items can be listed with
+    /// [Container::cached_keys()](struct.Container.html#method.cached_keys) and
+    /// dropped with [Container::evict()](struct.Container.html#method.evict).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::rc::Rc;
+    /// # use kamikaze_di::ContainerBuilder;
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// struct Logger { module: &'static str }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_keyed_builder::<&'static str, Rc<Logger>, _>(|_, module| {
+    ///     Rc::new(Logger { module })
+    /// })?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let logger = container.resolve_keyed::<Rc<Logger>, _>(&"app::db")?;
+    ///
+    /// assert_eq!("app::db", logger.module);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_keyed_builder<K, T, B>(&mut self, builder: B) -> Result<()>
+    where
+        B: (FnMut(&Container, &K) -> T) + Clone + 'static,
+        K: Eq + Hash + 'static,
+        T: 'static,
+    {
+        self.insert::<KeyedBy<K, T>>(Entry::keyed::<K, T, B>(builder))
+    }
+
+    /// Registers the [AssistedFactory](trait.AssistedFactory.html) of T.
+    ///
+    /// Items are created with
+    /// [Container::resolve_with()](struct.Container.html#method.resolve_with),
+    /// like for [register_factory_with()](struct.ContainerBuilder.html#method.register_factory_with).
+    /// See AssistedFactory for examples.
+    #[track_caller]
+    pub fn register_assisted_factory<T: AssistedFactory>(&mut self) -> Result<()> {
+        self.insert::<WithArgs<T::Args, T>>(Entry::factory_with(T::create))?;
+        self.dependencies
+            .declare::<WithArgs<T::Args, T>>(&T::dependencies());
+
+        Ok(())
     }
 
     /// Registers a builder.
@@ -234,21 +806,293 @@ impl ContainerBuilder {
     /// # Ok(())
     /// # }
     /// ```
+    #[track_caller]
     pub fn register_builder<T, B>(&mut self, builder: B) -> Result<()>
     where
         B: (FnOnce(&Container) -> T) + 'static,
         T: 'static,
     {
-        debug!("registering buiilder");
+        self.insert::<T>(Entry::registered::<T>(Resolver::builder(builder)))
+    }
+
+    /// Registers a builder whose item expires after ttl.
+    ///
+    /// The builder runs on the first resolve, and again on the first
+    /// resolve after the item expired or was
+    /// [invalidated](struct.Container.html#method.invalidate). In between,
+    /// the item is cloned. Time comes from the [Clock](trait.Clock.html)
+    /// registered as `Rc<dyn Clock>`, or the system clock if there is none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::rc::Rc;
+    /// # use std::time::Duration;
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// struct Credentials { token: String }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_builder_ttl::<Rc<Credentials>, _>(Duration::from_secs(900), |_| {
+    ///     Rc::new(Credentials { token: "secret".to_string() })
+    /// })?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let credentials = container.resolve::<Rc<Credentials>>()?;
+    /// let same_credentials = container.resolve::<Rc<Credentials>>()?;
+    ///
+    /// assert!(Rc::ptr_eq(&credentials, &same_credentials));
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_builder_ttl<T, B>(&mut self, ttl: Duration, builder: B) -> Result<()>
+    where
+        B: (FnMut(&Container) -> T) + Clone + 'static,
+        T: Clone + 'static,
+    {
+        self.insert::<T>(Entry::ttl::<T, B>(ttl, builder))
+    }
+
+    /// Registers a dependency that does not implement Clone.
+    ///
+    /// The item is kept in an Rc, and it's resolved as Rc<T>.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::rc::Rc;
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// // does not implement Clone or Copy
+    /// struct Keeper { x: i32 }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_shared(Keeper { x: 42 })?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let keeper = container.resolve::<Rc<Keeper>>()?;
+    /// assert_eq!(42, keeper.x);
+    ///
+    /// // T and Rc<T> are different types
+    /// let error = container.resolve_ref::<Keeper>().err().unwrap();
+    /// assert!(error.to_string().contains("resolve that instead"));
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_shared<T: 'static>(&mut self, item: T) -> Result<()> {
+        self.register::<Rc<T>>(Rc::new(item))
+    }
+
+    /// Registers a builder for a dependency that does not implement Clone.
+    ///
+    /// Works like [register_builder()](struct.ContainerBuilder.html#method.register_builder),
+    /// but the item is resolved as Rc<T>, like with
+    /// [register_shared()](struct.ContainerBuilder.html#method.register_shared).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::rc::Rc;
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// struct Keeper { x: i32 }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_shared_builder(|_| Keeper { x: 42 })?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let keeper = container.resolve::<Rc<Keeper>>()?;
+    /// assert_eq!(42, keeper.x);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_shared_builder<T, B>(&mut self, builder: B) -> Result<()>
+    where
+        B: (FnOnce(&Container) -> T) + 'static,
+        T: 'static,
+    {
+        self.register_builder::<Rc<T>, _>(move |container| Rc::new(builder(container)))
+    }
+
+    /// Installs a [Module](trait.Module.html), after the modules it requires.
+    ///
+    /// Modules that are already installed are skipped. Registrations
+    /// made by a module can be told apart with
+    /// [Registration::module()](struct.Registration.html#method.module).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, Module, Result};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// struct Config;
+    ///
+    /// impl Module for Config {
+    ///     fn configure(&self, builder: &mut ContainerBuilder) -> Result<()> {
+    ///         builder.register::<u32>(42)
+    ///     }
+    /// }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.install(Config)?;
+    /// builder.install(Config)?;
+    ///
+    /// assert_eq!(1, builder.installed_modules().len());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn install<M: Module>(&mut self, module: M) -> Result<()> {
+        self.install_module(&module)
+    }
+
+    /// Names of the installed modules, in the order they were installed.
+    pub fn installed_modules(&self) -> &[&'static str] {
+        &self.modules
+    }
+
+    fn install_module(&mut self, module: &dyn Module) -> Result<()> {
+        let name = module.name();
 
-        // We use double boxes so we can downcast to the inner box type.
-        // you can only downcast to Sized types, that's why we need an inner box
-        // see consume_builder() for use.
-        let boxed: Box<dyn (FnOnce(&Container) -> T) + 'static> = Box::new(builder);
-        let boxed: Box<dyn Any> = Box::new(boxed);
-        let resolver = Resolver::Builder(boxed);
+        if self.modules.contains(&name) || self.installing.contains(&name) {
+            return Ok(());
+        }
+
+        self.installing.push(name);
+        let installed = self.configure_module(module);
+        self.installing.pop();
+
+        match installed {
+            Ok(()) => {
+                self.modules.push(name);
+                Ok(())
+            }
+            Err(error) => {
+                Err(error.with_message(format!("Could not install module {}: {}", name, error)))
+            }
+        }
+    }
+
+    fn configure_module(&mut self, module: &dyn Module) -> Result<()> {
+        for required in module.requires() {
+            self.install_module(required.as_ref())?;
+        }
 
-        self.insert::<T>(resolver)
+        module.configure(self)
+    }
+
+    /// Moves everything registered in other into this builder, leaving
+    /// other empty.
+    ///
+    /// Types registered by both builders are handled according to policy,
+    /// and returned as [Conflict](struct.Conflict.html)s. With
+    /// [ConflictPolicy::Error](enum.ConflictPolicy.html#variant.Error),
+    /// nothing gets merged if there are conflicts, other is left as it
+    /// was, and the error lists them all.
+    ///
+    /// Observers, installed modules, profiles and skipped registrations
+    /// are merged too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ConflictPolicy, ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut core = ContainerBuilder::new();
+    /// core.register::<u16>(3306)?;
+    ///
+    /// let mut web = ContainerBuilder::new();
+    /// web.register::<u16>(8080)?;
+    /// web.register::<&str>("localhost")?;
+    ///
+    /// let conflicts = core.merge(&mut web, ConflictPolicy::KeepFirst)?;
+    /// assert_eq!("u16", conflicts[0].type_name());
+    ///
+    /// let container = core.build();
+    ///
+    /// assert_eq!(3306, container.resolve::<u16>()?);
+    /// assert_eq!("localhost", container.resolve::<&str>()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn merge(
+        &mut self,
+        other: &mut ContainerBuilder,
+        policy: ConflictPolicy,
+    ) -> Result<Vec<Conflict>> {
+        let mut conflicts: Vec<Conflict> = other
+            .entries
+            .iter()
+            .filter_map(|(type_id, entry)| {
+                let first = self.entries.get(type_id)?;
+
+                Some(Conflict::new(first, entry))
+            })
+            .collect();
+        conflicts.sort_by_key(|conflict| conflict.type_name());
+
+        if policy == ConflictPolicy::Error && !conflicts.is_empty() {
+            let conflicts: Vec<String> = conflicts
+                .iter()
+                .map(|conflict| conflict.to_string())
+                .collect();
+
+            return Err(format!(
+                "Could not merge builders, conflicting registrations:\n{}",
+                conflicts.join("\n")
+            )
+            .into());
+        }
+
+        let mut other = std::mem::take(other);
+
+        for (type_id, entry) in other.entries.drain() {
+            match self.entries.get_mut(&type_id) {
+                Some(first) => first.merge(entry, policy),
+                None => {
+                    self.entries.insert(type_id, entry);
+                }
+            }
+        }
+
+        self.observers.append(other.observers);
+        self.dependencies.append(other.dependencies);
+        self.roots.append(&mut other.roots);
+        self.skipped.append(&mut other.skipped);
+
+        for module in other.modules {
+            if !self.modules.contains(&module) {
+                self.modules.push(module);
+            }
+        }
+
+        for profile in other.profiles {
+            if !self.is_profile_active(&profile) {
+                self.profiles.push(profile);
+            }
+        }
+
+        Ok(conflicts)
     }
 
     /// Returns true if a dependency is registered.
@@ -267,26 +1111,91 @@ impl ContainerBuilder {
     pub fn has<T: 'static>(&self) -> bool {
         let type_id = TypeId::of::<T>();
 
-        self.resolvers.contains_key(&type_id)
+        self.entries.contains_key(&type_id)
     }
 
-    fn insert<T: 'static>(&mut self, resolver: Resolver) -> Result<()> {
-        debug!("inserting new object");
+    /// Lists everything registered so far, sorted by type name.
+    ///
+    /// See [Registration](struct.Registration.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, ResolverKind};
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register::<i16>(43);
+    /// builder.register_factory::<i32, _>(|_| 42);
+    ///
+    /// let registrations = builder.registrations();
+    ///
+    /// assert_eq!("i16", registrations[0].type_name());
+    /// assert_eq!(ResolverKind::Shared, registrations[0].kind());
+    /// assert_eq!(ResolverKind::Factory, registrations[1].kind());
+    /// ```
+    pub fn registrations(&self) -> Vec<Registration> {
+        Registration::collect(&self.entries)
+    }
 
+    /// Gives a registered type a name, so it can be resolved with
+    /// [Container::resolve_by_name()](struct.Container.html#method.resolve_by_name).
+    ///
+    /// Names are unique, and every type has at most one name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register::<&str>("mysql://primary")?;
+    /// builder.register_name::<&str>("db.primary")?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let url = container.resolve_by_name("db.primary")?;
+    ///
+    /// assert_eq!(Some(&"mysql://primary"), url.downcast_ref::<&str>());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_name<T: 'static>(&mut self, name: impl Into<String>) -> Result<()> {
+        name_entry(
+            &mut self.entries,
+            TypeId::of::<T>(),
+            type_name::<T>(),
+            name.into(),
+        )
+    }
+
+    /// Overrides where a type was registered.
+    pub(super) fn registered_at(&mut self, type_id: TypeId, location: &'static Location<'static>) {
+        if let Some(entry) = self.entries.get_mut(&type_id) {
+            entry.registered_at = Some(location);
+        }
+    }
+
+    fn insert<T: 'static>(&mut self, mut entry: Entry) -> Result<()> {
         let type_id = TypeId::of::<T>();
 
         if self.has::<T>() {
             return Err(format!("Container already has {:?}", type_id).into());
         }
 
-        self.resolvers.insert(type_id, resolver);
+        entry.module = self.installing.last().copied();
+        self.entries.insert(type_id, entry);
 
         Ok(())
     }
 }
 
-fn auto_factory<T: Inject>(container: &Container) -> T {
-    debug!("creating object in auto factory");
-
+pub(super) fn auto_factory<T: Inject>(container: &Container) -> T {
     T::resolve(container).unwrap()
 }
+
+fn initialize_injected<T: Injectable>(container: &Container) -> Result<()> {
+    T::inject(container).map(|_| ())
+}
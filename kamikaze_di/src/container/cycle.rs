@@ -24,7 +24,7 @@ impl CycleStopper {
 
         CycleGuard {
             guarded_type: type_id,
-            stopper: &self,
+            stopper: self,
         }
     }
 
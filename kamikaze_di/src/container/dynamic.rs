@@ -0,0 +1,232 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{Container, Entry, ResolverKind};
+use crate::{Error, Result};
+
+/// Gives the entry of type_id a name, if the name is not taken.
+pub(super) fn name_entry(
+    entries: &mut HashMap<TypeId, Entry>,
+    type_id: TypeId,
+    type_name: &str,
+    name: String,
+) -> Result<()> {
+    if let Some(other) = entries
+        .values()
+        .find(|entry| entry.name.as_ref() == Some(&name))
+    {
+        return Err(format!("Name {} is already used by {}", name, other.type_name).into());
+    }
+
+    match entries.get_mut(&type_id) {
+        Some(entry) => {
+            entry.name = Some(name);
+
+            Ok(())
+        }
+        None => Err(Error::not_registered(format!(
+            "Type not registered: {}",
+            type_name
+        ))),
+    }
+}
+
+impl Container {
+    /// Resolves a registered type by its id, for when the type is only
+    /// known at runtime.
+    ///
+    /// The item goes through the same entry as
+    /// [resolve()](trait.Resolver.html#tymethod.resolve), and can be
+    /// downcast to the registered type. Factories and builders are called
+    /// as usual, and the item is put in an Rc. Types that were never
+    /// registered or injected can't be resolved, since the container
+    /// can't name them. Neither can per graph factories and keyed builders.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::any::TypeId;
+    /// # use kamikaze_di::ContainerBuilder;
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register::<u32>(42)?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let item = container.resolve_dyn(TypeId::of::<u32>())?;
+    ///
+    /// assert_eq!(Some(&42), item.downcast_ref::<u32>());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_dyn(&self, type_id: TypeId) -> Result<Rc<dyn Any>> {
+        let dynamic = self
+            .entries
+            .borrow()
+            .get(&type_id)
+            .map(|entry| entry.dynamic);
+
+        match dynamic {
+            Some(resolve) => resolve(self),
+            None => Err(Error::not_registered(format!(
+                "Type not registered: {:?}",
+                type_id
+            ))),
+        }
+    }
+
+    /// Resolves a type by the name given to it with
+    /// [register_name()](struct.ContainerBuilder.html#method.register_name).
+    ///
+    /// Works like [resolve_dyn()](struct.Container.html#method.resolve_dyn).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::rc::Rc;
+    /// # use kamikaze_di::ContainerBuilder;
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// struct Database { url: String }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_shared(Database { url: "mysql://primary".to_string() })?;
+    /// builder.register_name::<Rc<Database>>("db.primary")?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let item = container.resolve_by_name("db.primary")?;
+    /// let database = item.downcast_ref::<Rc<Database>>().unwrap();
+    ///
+    /// assert_eq!("mysql://primary", database.url);
+    /// assert_eq!(vec!["db.primary"], container.names());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_by_name(&self, name: &str) -> Result<Rc<dyn Any>> {
+        let dynamic = self
+            .entries
+            .borrow()
+            .values()
+            .find(|entry| entry.name.as_deref() == Some(name))
+            .map(|entry| entry.dynamic);
+
+        match dynamic {
+            Some(resolve) => resolve(self),
+            None => Err(Error::not_registered(format!(
+                "Name not registered: {}",
+                name
+            ))),
+        }
+    }
+
+    /// Lists the names given with
+    /// [register_name()](struct.ContainerBuilder.html#method.register_name),
+    /// sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .entries
+            .borrow()
+            .values()
+            .filter_map(|entry| entry.name.clone())
+            .collect();
+
+        names.sort();
+
+        names
+    }
+
+    /// Resolves T into an Rc<dyn Any>, see Entry::dynamic.
+    pub(super) fn get_dyn<T: 'static>(&self) -> Result<Rc<dyn Any>> {
+        let type_id = TypeId::of::<T>();
+
+        self.tracked::<T, _, _>(|kind| match kind {
+            Some(ResolverKind::Factory) | Some(ResolverKind::Ttl) => {
+                let item: Rc<dyn Any> = Rc::new(self.call_factory::<T>(type_id)?);
+
+                Ok(item)
+            }
+            Some(ResolverKind::Builder) => {
+                self.consume_builder::<T>()?;
+                Ok(self.shared_item(type_id))
+            }
+            Some(ResolverKind::Shared)
+            | Some(ResolverKind::Injected)
+            | Some(ResolverKind::PerThread) => Ok(self.shared_item(type_id)),
+            Some(ResolverKind::PerGraph) | Some(ResolverKind::Keyed) => {
+                Err(format!("Type {} can't be resolved dynamically", type_name::<T>()).into())
+            }
+            None => Err(self.not_registered::<T>()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::{Container, ContainerBuilder, Inject, Injector, Resolver, Result};
+
+    #[derive(Clone)]
+    struct Handler {
+        port: u16,
+    }
+
+    impl Inject for Handler {
+        fn resolve(container: &Container) -> Result<Self> {
+            Ok(Handler {
+                port: container.resolve()?,
+            })
+        }
+    }
+
+    #[test]
+    fn typed_and_dynamic_resolutions_share_entries() {
+        let mut builder = ContainerBuilder::new();
+        builder.register::<u16>(8080).unwrap();
+        builder
+            .register_builder::<Rc<Cell<u32>>, _>(|_| Rc::new(Cell::new(0)))
+            .unwrap();
+        builder.register_name::<Rc<Cell<u32>>>("counter").unwrap();
+
+        let container = builder.build();
+
+        let counter = container.resolve_by_name("counter").unwrap();
+        let counter = counter.downcast_ref::<Rc<Cell<u32>>>().unwrap();
+        counter.set(1);
+
+        assert_eq!(1, container.resolve::<Rc<Cell<u32>>>().unwrap().get());
+
+        let _: Handler = container.inject().unwrap();
+        let handler = container.resolve_dyn(TypeId::of::<Handler>()).unwrap();
+
+        assert_eq!(8080, handler.downcast_ref::<Handler>().unwrap().port);
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let container = Container::new();
+        container.register::<u16>(80).unwrap();
+        container.register::<u32>(80).unwrap();
+        container.register_name::<u16>("port").unwrap();
+
+        assert!(container.register_name::<u32>("port").is_err());
+        assert!(container
+            .register_name::<u64>("other")
+            .unwrap_err()
+            .is_not_registered());
+        assert!(container
+            .resolve_by_name("other")
+            .unwrap_err()
+            .is_not_registered());
+        assert_eq!(vec!["port"], container.names());
+    }
+}
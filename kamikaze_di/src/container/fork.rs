@@ -0,0 +1,242 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{Container, Entry, Resolver};
+use crate::Result;
+
+impl Container {
+    /// Creates a container with everything this one has, that can be
+    /// changed without affecting this one.
+    ///
+    /// Built items are shared with the fork. Factories, keyed builders
+    /// and builders with a time to live are not: the fork gets a copy of
+    /// them as they were registered, which starts without any items.
+    ///
+    /// Builders that were not built yet, pools included, are shared too.
+    /// fork() does not call them, the first container to resolve the
+    /// type does, with its own dependencies, and the other one reuses the
+    /// item.
+    ///
+    /// Use [replace()](struct.Container.html#method.replace),
+    /// [register()](struct.Container.html#method.register) and
+    /// [remove()](struct.Container.html#method.remove) to override things
+    /// in the fork.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register::<&str>("mysql")?;
+    /// builder.register_factory::<String, _>(|container| {
+    ///     format!("connected to {}", container.resolve::<&str>().unwrap())
+    /// })?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let fork = container.fork()?;
+    /// fork.replace::<&str>("in memory")?;
+    ///
+    /// assert_eq!("connected to in memory", fork.resolve::<String>()?);
+    /// assert_eq!("connected to mysql", container.resolve::<String>()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fork(&self) -> Result<Container> {
+        let mut entries = HashMap::new();
+
+        for (type_id, entry) in self.entries.borrow().iter() {
+            entries.insert(*type_id, entry.fork()?);
+        }
+
+        Ok(Container {
+            entries: RefCell::new(entries),
+            cycle_stopper: Default::default(),
+            observers: self.observers.clone(),
+            dependencies: self.dependencies.fork(),
+            lazy: Default::default(),
+            skipped: self.skipped.clone(),
+            graph_items: Default::default(),
+            weak: Default::default(),
+        })
+    }
+}
+
+impl Entry {
+    fn fork(&self) -> Result<Entry> {
+        let (resolver, expiry) = match (&self.forker, &self.resolver) {
+            (Some(forker), _) => (forker.0)(),
+            (None, Resolver::Shared(item)) => (Resolver::Shared(item.clone()), None),
+            (None, Resolver::Builder(pending)) => (Resolver::Builder(pending.clone()), None),
+            (None, _) => return Err(format!("Type {} can't be forked", self.type_name).into()),
+        };
+
+        let mut bindings = vec![];
+        for binding in &self.bindings {
+            bindings.push(Rc::new(RefCell::new(binding.borrow().fork()?)));
+        }
+
+        Ok(Entry {
+            resolver,
+            type_name: self.type_name,
+            kind: self.kind,
+            registered_at: self.registered_at,
+            module: self.module,
+            resolutions: Cell::new(self.resolutions.get()),
+            rc_type_id: self.rc_type_id,
+            bindings,
+            initialize: self.initialize,
+            expiry,
+            forker: self.forker.clone(),
+            pool_size: self.pool_size,
+            dynamic: self.dynamic,
+            name: self.name.clone(),
+            eager: self.eager,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use crate::{Container, ContainerBuilder, Inject, Injector, Resolver, Result};
+
+    #[derive(Clone)]
+    struct Service {
+        port: u32,
+    }
+
+    impl Inject for Service {
+        fn resolve(container: &Container) -> Result<Self> {
+            Ok(Service {
+                port: container.resolve()?,
+            })
+        }
+    }
+
+    #[test]
+    fn forks_share_built_items() {
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_builder::<Rc<Cell<u32>>, _>(|_| Rc::new(Cell::new(1)))
+            .unwrap();
+
+        let container = builder.build();
+        let counter: Rc<Cell<u32>> = container.resolve().unwrap();
+
+        let fork = container.fork().unwrap();
+        let forked: Rc<Cell<u32>> = fork.resolve().unwrap();
+
+        assert!(Rc::ptr_eq(&counter, &forked));
+    }
+
+    #[test]
+    fn builders_that_were_not_built_are_shared() {
+        let built = Rc::new(Cell::new(0));
+        let count = built.clone();
+
+        let mut builder = ContainerBuilder::new();
+        builder.register::<u32>(80).unwrap();
+        builder
+            .register_builder::<Rc<Service>, _>(move |container| {
+                count.set(count.get() + 1);
+                Rc::new(container.inject().unwrap())
+            })
+            .unwrap();
+        builder.register_pool::<Vec<u8>, _>(2, |_| vec![]).unwrap();
+
+        let container = builder.build();
+        let fork = container.fork().unwrap();
+        fork.replace::<u32>(8080).unwrap();
+
+        assert_eq!(0, built.get());
+        assert!(fork.has::<Rc<Service>>());
+
+        let forked: Rc<Service> = fork.resolve().unwrap();
+        let service: Rc<Service> = container.resolve().unwrap();
+
+        assert_eq!(8080, forked.port);
+        assert!(Rc::ptr_eq(&forked, &service));
+        assert_eq!(1, built.get());
+        assert_eq!(0, fork.checkout::<Vec<u8>>().unwrap().len());
+    }
+
+    #[test]
+    fn forks_get_their_own_factories() {
+        let mut builder = ContainerBuilder::new();
+        let mut next = 0;
+        builder
+            .register_factory::<u32, _>(move |_| {
+                next += 1;
+                next
+            })
+            .unwrap();
+
+        let container = builder.build();
+        assert_eq!(1, container.resolve::<u32>().unwrap());
+
+        let fork = container.fork().unwrap();
+
+        assert_eq!(1, fork.resolve::<u32>().unwrap());
+        assert_eq!(2, fork.resolve::<u32>().unwrap());
+        assert_eq!(2, container.resolve::<u32>().unwrap());
+    }
+
+    #[test]
+    fn forks_get_their_own_ttl_cache() {
+        let built = Rc::new(Cell::new(0));
+        let count = built.clone();
+
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_builder_ttl::<u32, _>(Duration::from_secs(3600), move |_| {
+                count.set(count.get() + 1);
+                count.get()
+            })
+            .unwrap();
+
+        let container = builder.build();
+        assert_eq!(1, container.resolve::<u32>().unwrap());
+
+        let fork = container.fork().unwrap();
+        assert_eq!(2, fork.resolve::<u32>().unwrap());
+
+        fork.invalidate::<u32>().unwrap();
+
+        assert_eq!(1, container.resolve::<u32>().unwrap());
+        assert_eq!(3, fork.resolve::<u32>().unwrap());
+    }
+
+    #[test]
+    fn overrides_stay_in_the_fork() {
+        let mut builder = ContainerBuilder::new();
+        builder.register::<u32>(80).unwrap();
+        builder
+            .register_keyed_builder::<u8, u32, _>(|container, key| {
+                container.resolve::<u32>().unwrap() + u32::from(*key)
+            })
+            .unwrap();
+
+        let container = builder.build();
+        assert_eq!(81, container.resolve_keyed::<u32, _>(&1u8).unwrap());
+
+        let fork = container.fork().unwrap();
+        fork.replace::<u32>(8080).unwrap();
+
+        let service: Service = fork.inject().unwrap();
+
+        assert_eq!(8080, service.port);
+        assert_eq!(8081, fork.resolve_keyed::<u32, _>(&1u8).unwrap());
+        assert_eq!(80, container.resolve::<u32>().unwrap());
+        assert_eq!(81, container.resolve_keyed::<u32, _>(&1u8).unwrap());
+        assert!(!container.has::<Service>());
+    }
+}
@@ -0,0 +1,665 @@
+use std::any::{type_name, TypeId};
+use std::cell::{RefCell, RefMut};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::panic::Location;
+
+use super::{Container, ResolverKind};
+use crate::Result;
+
+/// A type something depends on.
+///
+/// Derived [Inject](trait.Inject.html), [InjectAsRc](trait.InjectAsRc.html)
+/// and [InjectAsArc](trait.InjectAsArc.html) implementations list their
+/// fields as dependencies.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Dependency {
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+impl Dependency {
+    /// Creates the dependency on T.
+    pub fn of<T: ?Sized + 'static>() -> Dependency {
+        Dependency {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+        }
+    }
+
+    /// Id of the type depended on.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Name of the type depended on.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+/// Dependencies between the types known to a container.
+///
+/// Edges come from two places: they are either observed while the
+/// container resolves things, or declared by
+/// [Inject::dependencies()](trait.Inject.html#method.dependencies) and
+/// [InjectAsRc::dependencies()](trait.InjectAsRc.html#method.dependencies).
+///
+/// Nodes and edges are sorted by type name, so exports of the same
+/// wiring can be diffed. Nodes are distinct types, exports tell apart
+/// types with the same name by numbering them, like `Config #2`, in
+/// the order they were registered in the source, or first seen by the
+/// container for types registered elsewhere.
+///
+/// Declared edges are recorded when a type is registered through
+/// [register_automatic_factory()](struct.ContainerBuilder.html#method.register_automatic_factory),
+/// [register_assisted_factory()](struct.ContainerBuilder.html#method.register_assisted_factory)
+/// or [eager_root()](struct.ContainerBuilder.html#method.eager_root),
+/// the others only know about T once it is injected.
+///
+/// # Examples
+///
+/// ```
+/// # use kamikaze_di::{ContainerBuilder, Resolver};
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// let mut builder = ContainerBuilder::new();
+/// builder.register::<i16>(41)?;
+/// builder.register_factory::<i32, _>(|container| {
+///     let base: i16 = container.resolve().unwrap();
+///     i32::from(base) + 1
+/// })?;
+///
+/// let container = builder.build();
+/// container.resolve::<i32>()?;
+///
+/// let graph = container.dependency_graph();
+/// assert_eq!(2, graph.nodes().len());
+/// assert_eq!("i32", graph.edges()[0].from());
+/// assert_eq!("i16", graph.edges()[0].to());
+///
+/// assert!(graph.to_dot().contains(r#""i32" -> "i16";"#));
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DependencyGraph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+/// A type in the [DependencyGraph](struct.DependencyGraph.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GraphNode {
+    type_id: TypeId,
+    type_name: &'static str,
+    kind: Option<ResolverKind>,
+}
+
+/// A dependency in the [DependencyGraph](struct.DependencyGraph.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GraphEdge {
+    from: (TypeId, &'static str),
+    to: (TypeId, &'static str),
+    observed: bool,
+    declared: bool,
+}
+
+impl DependencyGraph {
+    /// Types in the graph.
+    pub fn nodes(&self) -> &[GraphNode] {
+        &self.nodes
+    }
+
+    /// Dependencies between types.
+    pub fn edges(&self) -> &[GraphEdge] {
+        &self.edges
+    }
+
+    /// Exports the graph to the Graphviz DOT format.
+    ///
+    /// Declared dependencies that were never observed are dashed.
+    pub fn to_dot(&self) -> String {
+        let names = self.unique_names();
+        let mut dot = String::from("digraph dependencies {\n");
+
+        for node in &self.nodes {
+            let name = &names[&node.type_id];
+            let label = match node.kind {
+                Some(kind) => format!("\"{}\\n({})\"", dot_escape(name), kind_name(kind)),
+                None => dot_id(name),
+            };
+
+            let _ = writeln!(dot, "    {} [label={}];", dot_id(name), label);
+        }
+
+        for edge in &self.edges {
+            let style = if edge.observed { "" } else { " [style=dashed]" };
+
+            let _ = writeln!(
+                dot,
+                "    {} -> {}{};",
+                dot_id(&names[&edge.from.0]),
+                dot_id(&names[&edge.to.0]),
+                style
+            );
+        }
+
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// Exports the graph to JSON.
+    ///
+    /// The format is:
+    /// ```json
+    /// {
+    ///   "nodes": [{"type": "i32", "kind": "factory"}],
+    ///   "edges": [{"from": "i32", "to": "i16", "observed": true, "declared": false}]
+    /// }
+    /// ```
+    /// where "kind" is one of "factory", "builder", "shared", "injected",
+    /// "keyed", "per_graph", "per_thread", "ttl" or null for types the
+    /// container does not know about.
+    pub fn to_json(&self) -> String {
+        let names = self.unique_names();
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let kind = match node.kind {
+                    Some(kind) => json_string(kind_name(kind)),
+                    None => "null".to_string(),
+                };
+
+                format!(
+                    "{{\"type\": {}, \"kind\": {}}}",
+                    json_string(&names[&node.type_id]),
+                    kind
+                )
+            })
+            .collect();
+
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                format!(
+                    "{{\"from\": {}, \"to\": {}, \"observed\": {}, \"declared\": {}}}",
+                    json_string(&names[&edge.from.0]),
+                    json_string(&names[&edge.to.0]),
+                    edge.observed,
+                    edge.declared
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"nodes\": [{}], \"edges\": [{}]}}",
+            nodes.join(", "),
+            edges.join(", ")
+        )
+    }
+
+    /// Names of the nodes, numbered when different types have the same name.
+    fn unique_names(&self) -> HashMap<TypeId, String> {
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+
+        self.nodes
+            .iter()
+            .map(|node| {
+                let count = seen.entry(node.type_name).or_insert(0);
+                *count += 1;
+
+                let name = match *count {
+                    1 => node.type_name.to_string(),
+                    count => format!("{} #{}", node.type_name, count),
+                };
+
+                (node.type_id, name)
+            })
+            .collect()
+    }
+}
+
+impl GraphNode {
+    /// Id of the type.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Name of the type.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// How the container resolves the type, None if it's not registered.
+    pub fn kind(&self) -> Option<ResolverKind> {
+        self.kind
+    }
+}
+
+impl GraphEdge {
+    /// Name of the dependent type.
+    pub fn from(&self) -> &'static str {
+        self.from.1
+    }
+
+    /// Name of the type depended on.
+    pub fn to(&self) -> &'static str {
+        self.to.1
+    }
+
+    /// Id of the dependent type.
+    pub fn from_type_id(&self) -> TypeId {
+        self.from.0
+    }
+
+    /// Id of the type depended on.
+    pub fn to_type_id(&self) -> TypeId {
+        self.to.0
+    }
+
+    /// True if the dependency was seen while resolving.
+    pub fn is_observed(&self) -> bool {
+        self.observed
+    }
+
+    /// True if the dependency was declared by an Inject, InjectAsRc or InjectAsArc impl.
+    pub fn is_declared(&self) -> bool {
+        self.declared
+    }
+}
+
+impl Container {
+    /// Returns the dependencies recorded so far.
+    ///
+    /// See [DependencyGraph](struct.DependencyGraph.html).
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        let mut nodes: HashMap<TypeId, GraphNode> = HashMap::new();
+        let mut locations: HashMap<TypeId, &'static Location<'static>> = HashMap::new();
+
+        for registration in self.registrations() {
+            let node = GraphNode {
+                type_id: registration.type_id(),
+                type_name: registration.type_name(),
+                kind: Some(registration.kind()),
+            };
+
+            if let Some(location) = registration.registered_at() {
+                locations.insert(node.type_id, location);
+            }
+            nodes.insert(node.type_id, node);
+        }
+
+        let mut edges: Vec<GraphEdge> =
+            self.dependencies.edges.borrow().values().cloned().collect();
+
+        for edge in &edges {
+            for &(type_id, type_name) in &[edge.from, edge.to] {
+                nodes.entry(type_id).or_insert(GraphNode {
+                    type_id,
+                    type_name,
+                    kind: None,
+                });
+            }
+        }
+
+        // TypeIds change between compilations, ties between types with
+        // the same name are broken by where they were registered instead.
+        let seen = self.dependencies.seen.borrow();
+        let mut nodes: Vec<GraphNode> = nodes.into_values().collect();
+        nodes.sort_by_key(|node| {
+            let location = locations
+                .get(&node.type_id)
+                .map(|location| (location.file(), location.line(), location.column()));
+            let seen = seen.get(&node.type_id).copied().unwrap_or(usize::MAX);
+
+            (node.type_name, location.is_none(), location, seen)
+        });
+
+        let rank: HashMap<TypeId, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(rank, node)| (node.type_id, rank))
+            .collect();
+        edges.sort_by_key(|edge| (rank[&edge.from.0], rank[&edge.to.0]));
+
+        DependencyGraph { nodes, edges }
+    }
+}
+
+/// Records dependencies between types, see Container::dependency_graph().
+#[derive(Debug, Default)]
+pub(crate) struct DependencyRecorder {
+    resolving: RefCell<Vec<(TypeId, &'static str)>>,
+    edges: RefCell<HashMap<(TypeId, TypeId), GraphEdge>>,
+    /// Order types were first seen in, for types with the same name.
+    seen: RefCell<HashMap<TypeId, usize>>,
+}
+
+impl DependencyRecorder {
+    /// Records dependencies declared by T.
+    pub fn declare<T: 'static>(&self, dependencies: &[Dependency]) {
+        let from = (TypeId::of::<T>(), type_name::<T>());
+        self.see(from.0);
+
+        for dependency in dependencies {
+            let to = (dependency.type_id, dependency.type_name);
+
+            self.see(to.0);
+            self.edge(from, to).declared = true;
+        }
+    }
+
+    /// Runs resolve() for T, recording a dependency on T for the type
+    /// being resolved at the moment, if any.
+    pub fn record<T, R, F>(&self, resolve: F) -> Result<R>
+    where
+        T: 'static,
+        F: FnOnce() -> Result<R>,
+    {
+        let current = (TypeId::of::<T>(), type_name::<T>());
+        let parent = self.resolving.borrow().last().cloned();
+        self.see(current.0);
+
+        if let Some(parent) = parent {
+            self.edge(parent, current).observed = true;
+        }
+
+        let _guard = ResolvingGuard::new(self, current);
+
+        resolve()
+    }
+
+    /// Adds the dependencies recorded by other.
+    pub fn append(&self, other: DependencyRecorder) {
+        let mut other_seen: Vec<_> = other.seen.into_inner().into_iter().collect();
+        other_seen.sort_by_key(|&(_, order)| order);
+        for (type_id, _) in other_seen {
+            self.see(type_id);
+        }
+
+        let mut edges = self.edges.borrow_mut();
+
+        for (key, edge) in other.edges.into_inner() {
+            let merged = edges.entry(key).or_insert(edge);
+            merged.observed |= edge.observed;
+            merged.declared |= edge.declared;
+        }
+    }
+
+    /// A recorder with the same dependencies, that is not resolving anything.
+    pub fn fork(&self) -> DependencyRecorder {
+        DependencyRecorder {
+            resolving: Default::default(),
+            edges: self.edges.clone(),
+            seen: self.seen.clone(),
+        }
+    }
+
+    /// Orders items so that the ones T declares dependencies on, directly
+    /// or through other types, come before T. Keeps the order otherwise.
+    pub fn dependencies_first<I>(&self, items: Vec<(TypeId, I)>) -> Vec<(TypeId, I)> {
+        let mut declared: HashMap<TypeId, Vec<TypeId>> = HashMap::new();
+        for edge in self.edges.borrow().values().filter(|edge| edge.declared) {
+            declared.entry(edge.from.0).or_default().push(edge.to.0);
+        }
+
+        let mut positions: HashMap<TypeId, Vec<usize>> = HashMap::new();
+        for (position, (type_id, _)) in items.iter().enumerate() {
+            positions.entry(*type_id).or_default().push(position);
+        }
+
+        let seen = self.seen.borrow();
+        let rank = |type_id: &TypeId| {
+            let position = positions
+                .get(type_id)
+                .map_or(usize::MAX, |positions| positions[0]);
+
+            (position, seen.get(type_id).copied().unwrap_or(usize::MAX))
+        };
+
+        let mut order = vec![];
+        let mut visited = HashSet::new();
+        let mut visit_all = |type_id: TypeId| {
+            let mut stack = vec![(type_id, false)];
+
+            while let Some((type_id, dependencies_done)) = stack.pop() {
+                if dependencies_done {
+                    order.extend(positions.get(&type_id).into_iter().flatten().copied());
+                    continue;
+                }
+                if !visited.insert(type_id) {
+                    continue;
+                }
+
+                let mut dependencies = declared.get(&type_id).cloned().unwrap_or_default();
+                dependencies.sort_by_key(|dependency| std::cmp::Reverse(rank(dependency)));
+
+                stack.push((type_id, true));
+                stack.extend(
+                    dependencies
+                        .into_iter()
+                        .map(|dependency| (dependency, false)),
+                );
+            }
+        };
+
+        for (type_id, _) in &items {
+            visit_all(*type_id);
+        }
+
+        let mut items: Vec<Option<(TypeId, I)>> = items.into_iter().map(Some).collect();
+
+        order
+            .into_iter()
+            .filter_map(|position| items[position].take())
+            .collect()
+    }
+
+    /// True while a type is being resolved.
+    pub fn is_resolving(&self) -> bool {
+        !self.resolving.borrow().is_empty()
+    }
+
+    /// True while T is being resolved.
+    pub fn is_resolving_type(&self, type_id: TypeId) -> bool {
+        self.resolving.borrow().iter().any(|(id, _)| *id == type_id)
+    }
+
+    fn see(&self, type_id: TypeId) {
+        let mut seen = self.seen.borrow_mut();
+        let order = seen.len();
+
+        seen.entry(type_id).or_insert(order);
+    }
+
+    fn edge(
+        &self,
+        from: (TypeId, &'static str),
+        to: (TypeId, &'static str),
+    ) -> RefMut<'_, GraphEdge> {
+        RefMut::map(self.edges.borrow_mut(), |edges| {
+            edges.entry((from.0, to.0)).or_insert(GraphEdge {
+                from,
+                to,
+                observed: false,
+                declared: false,
+            })
+        })
+    }
+}
+
+/// Keeps track of the type being resolved, even when resolving panics.
+struct ResolvingGuard<'a> {
+    recorder: &'a DependencyRecorder,
+}
+
+impl<'a> ResolvingGuard<'a> {
+    fn new(recorder: &'a DependencyRecorder, current: (TypeId, &'static str)) -> Self {
+        recorder.resolving.borrow_mut().push(current);
+
+        ResolvingGuard { recorder }
+    }
+}
+
+impl<'a> Drop for ResolvingGuard<'a> {
+    fn drop(&mut self) {
+        self.recorder.resolving.borrow_mut().pop();
+    }
+}
+
+fn kind_name(kind: ResolverKind) -> &'static str {
+    match kind {
+        ResolverKind::Factory => "factory",
+        ResolverKind::Builder => "builder",
+        ResolverKind::Shared => "shared",
+        ResolverKind::Injected => "injected",
+        ResolverKind::Keyed => "keyed",
+        ResolverKind::PerGraph => "per_graph",
+        ResolverKind::PerThread => "per_thread",
+        ResolverKind::Ttl => "ttl",
+    }
+}
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", dot_escape(name))
+}
+
+fn dot_escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn json_string(string: &str) -> String {
+    let mut json = String::from("\"");
+
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{Container, ContainerBuilder, Dependency, Inject, Injector, Resolver, Result};
+
+    #[derive(Clone)]
+    struct Service;
+
+    impl Inject for Service {
+        fn resolve(container: &Container) -> Result<Self> {
+            let _: i32 = container.resolve()?;
+
+            Ok(Service)
+        }
+
+        fn dependencies() -> Vec<Dependency> {
+            vec![Dependency::of::<i32>(), Dependency::of::<u8>()]
+        }
+    }
+
+    fn graph() -> super::DependencyGraph {
+        let mut builder = ContainerBuilder::new();
+        builder.register::<i32>(42).unwrap();
+
+        let container = builder.build();
+        let _: Service = container.inject().unwrap();
+
+        container.dependency_graph()
+    }
+
+    #[test]
+    fn merges_observed_and_declared_edges() {
+        let edges: Vec<_> = graph()
+            .edges()
+            .iter()
+            .map(|edge| (edge.to(), edge.is_observed(), edge.is_declared()))
+            .collect();
+
+        assert_eq!(vec![("i32", true, true), ("u8", false, true)], edges);
+    }
+
+    #[test]
+    fn exports_to_json() {
+        let service = std::any::type_name::<Service>();
+        let expected = format!(
+            concat!(
+                r#"{{"nodes": [{{"type": "i32", "kind": "shared"}}, "#,
+                r#"{{"type": "{0}", "kind": "injected"}}, {{"type": "u8", "kind": null}}], "#,
+                r#""edges": [{{"from": "{0}", "to": "i32", "observed": true, "declared": true}}, "#,
+                r#"{{"from": "{0}", "to": "u8", "observed": false, "declared": true}}]}}"#
+            ),
+            service
+        );
+
+        assert_eq!(expected, graph().to_json());
+    }
+
+    #[test]
+    fn exports_to_dot() {
+        let dot = graph().to_dot();
+        let service = std::any::type_name::<Service>();
+
+        assert!(dot.starts_with("digraph dependencies {\n"));
+        assert!(dot.contains(r#""i32" [label="i32\n(shared)"];"#));
+        assert!(dot.contains(&format!(r#""{}" -> "u8" [style=dashed];"#, service)));
+    }
+
+    #[test]
+    fn types_with_the_same_name_are_different_nodes() {
+        let container = Container::new();
+
+        {
+            #[derive(Clone)]
+            struct Config;
+            container.register(Config).unwrap();
+        }
+        {
+            #[derive(Clone)]
+            struct Config;
+            container
+                .register_builder_ttl(Duration::from_secs(1), |_| Config)
+                .unwrap();
+        }
+
+        let graph = container.dependency_graph();
+        let json = graph.to_json();
+
+        assert_eq!(2, graph.nodes().len());
+        assert_ne!(graph.nodes()[0].type_id(), graph.nodes()[1].type_id());
+        assert!(json.contains(r#"::Config", "kind": "shared""#));
+        assert!(json.contains(r#"::Config #2", "kind": "ttl""#));
+    }
+
+    #[test]
+    fn declared_edges_are_recorded_on_registration() {
+        let mut builder = ContainerBuilder::new();
+        builder.register::<i32>(42).unwrap();
+        builder.eager_root::<Service>();
+
+        let graph = builder.build().dependency_graph();
+        let edges: Vec<_> = graph
+            .edges()
+            .iter()
+            .map(|edge| (edge.to(), edge.is_observed(), edge.is_declared()))
+            .collect();
+
+        assert_eq!(vec![("i32", false, true), ("u8", false, true)], edges);
+    }
+}
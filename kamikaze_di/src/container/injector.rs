@@ -1,20 +1,79 @@
-use std::rc::Rc;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::ffi::OsString;
+use std::marker::PhantomData;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use super::private::Sealed;
+use crate::container::graph::Dependency;
+use crate::container::lazy::Lazy;
 use crate::container::Container;
-use crate::Result;
+use crate::{Error, Result};
 
 /// Resolves dependencies automatically.
 ///
 /// This trait allows the container to resolve some types without
 /// them having to be registered beforehand.
 ///
-/// See the [Inject](trait.Inject.html) trait for examples.
+/// Any [Injectable](trait.Injectable.html) type can be injected.
+/// Registered types are resolved from the container first, the others
+/// get injected. See the [Inject](trait.Inject.html) trait for examples.
+/// Registered types that can't implement Injectable, like
+/// `Rc<dyn Trait>`, can be injected with the [inject!](macro.inject.html)
+/// macro.
 pub trait Injector<T>: Sealed {
     /// Produces T.
     fn inject(&self) -> Result<T>;
 }
 
+/// Types that can be produced by [Injector::inject()](trait.Injector.html#tymethod.inject).
+///
+/// Types the container can build implement [Inject](trait.Inject.html),
+/// [InjectAsRc](trait.InjectAsRc.html) or
+/// [InjectAsArc](trait.InjectAsArc.html) instead. Types that are only
+/// ever registered can implement this with an empty impl, std types,
+/// tuples and collections already do:
+/// ```
+/// use kamikaze_di::{ContainerBuilder, Injectable, Injector};
+///
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// #[derive(Clone)]
+/// struct Config { db: String }
+///
+/// impl Injectable for Config {}
+///
+/// let mut container_builder = ContainerBuilder::new();
+/// container_builder.register(Config { db: "localhost".to_string() })?;
+///
+/// let container = container_builder.build();
+/// let config: Config = container.inject()?;
+///
+/// assert_eq!("localhost", config.db);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub trait Injectable: Clone + 'static {
+    /// Produces Self from the container.
+    ///
+    /// Resolves the registered Self by default.
+    fn inject(container: &Container) -> Result<Self> {
+        container.get()
+    }
+
+    /// Lists the types needed to inject Self.
+    ///
+    /// Forwards to [Inject::dependencies()](trait.Inject.html#method.dependencies)
+    /// for Inject types, none otherwise.
+    fn dependencies() -> Vec<Dependency> {
+        vec![]
+    }
+}
+
 /// Resolves itself from a container.
 ///
 /// Allows the type to be resolved by the container without having to
@@ -59,6 +118,15 @@ where
 {
     /// Resolve Self from a Container.
     fn resolve(container: &Container) -> Result<Self>;
+
+    /// Lists the types needed to resolve Self.
+    ///
+    /// Only used for building the
+    /// [dependency graph](struct.Container.html#method.dependency_graph),
+    /// derived implementations list the types of all fields.
+    fn dependencies() -> Vec<Dependency> {
+        vec![]
+    }
 }
 
 /// Resolves itself from a container as a Rc<T>.
@@ -108,15 +176,80 @@ where
     ///
     /// The object will be Rc-ed inside the container.
     fn resolve(container: &Container) -> Result<Self>;
+
+    /// Lists the types needed to resolve Self.
+    ///
+    /// See [Inject::dependencies()](trait.Inject.html#method.dependencies).
+    fn dependencies() -> Vec<Dependency> {
+        vec![]
+    }
 }
 
-impl<T> Injector<T> for Container
+/// Resolves itself from a container as an Arc<T>.
+///
+/// Works like [InjectAsRc](trait.InjectAsRc.html), for types that get
+/// shared with other threads. The container itself is not Sync, but
+/// the Arc<T> it hands out can be sent to other threads if T allows it.
+/// The container keeps one Arc<T>, every injection clones it.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use kamikaze_di::{Result, Container, ContainerBuilder, InjectAsArc, Injector};
+///
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// struct Pool { size: usize }
+///
+/// impl InjectAsArc for Pool {
+///     fn resolve(container: &Container) -> Result<Self> {
+///         Ok(Pool { size: container.inject()? })
+///     }
+/// }
+///
+/// let mut container_builder = ContainerBuilder::new();
+/// container_builder.register::<usize>(4);
+///
+/// let container = container_builder.build();
+///
+/// let pool: Arc<Pool> = container.inject()?;
+/// let same_pool: Arc<Pool> = container.inject()?;
+/// assert!(Arc::ptr_eq(&pool, &same_pool));
+///
+/// let size = thread::spawn(move || pool.size).join().unwrap();
+/// assert_eq!(4, size);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub trait InjectAsArc
 where
-    T: Clone + 'static,
+    Self: Sized,
 {
-    default fn inject(&self) -> Result<T> {
-        debug!("injecting registered type");
-        self.get()
+    /// Resolve Self from a Container.
+    ///
+    /// The object will be Arc-ed inside the container.
+    fn resolve(container: &Container) -> Result<Self>;
+
+    /// Lists the types needed to resolve Self.
+    ///
+    /// See [Inject::dependencies()](trait.Inject.html#method.dependencies).
+    fn dependencies() -> Vec<Dependency> {
+        vec![]
+    }
+}
+
+impl<T: Injectable> Injector<T> for Container {
+    fn inject(&self) -> Result<T> {
+        self.dependencies.declare::<T>(&T::dependencies());
+
+        if self.has::<T>() {
+            return self.get();
+        }
+
+        T::inject(self)
     }
 }
 
@@ -128,52 +261,339 @@ where
 //    }
 //}
 
-impl<T> Injector<T> for Container
+impl<T> Injectable for T
 where
     T: Inject + Clone + 'static,
 {
-    fn inject(&self) -> Result<T> {
-        debug!("injecting Inject type");
+    fn inject(container: &Container) -> Result<T> {
+        if container.has::<T>() {
+            return container.get();
+        }
 
-        if !self.has::<T>() {
-            debug!("Inject type not known, auto-resolving");
-            let item = T::resolve(self)?;
+        container.auto_inject(T::resolve)
+    }
 
-            use super::Resolver;
-            let resolver = Resolver::Shared(Box::new(item));
+    fn dependencies() -> Vec<Dependency> {
+        <T as Inject>::dependencies()
+    }
+}
 
-            self.insert::<T>(resolver)?;
+/// Optional dependencies.
+///
+/// Resolves to None when T, or something T depends on, is not
+/// registered. Other errors are returned as they are.
+///
+/// # Examples
+///
+/// ```
+/// use kamikaze_di::{ContainerBuilder, Injectable, Injector};
+///
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// #[derive(Clone)]
+/// struct Metrics;
+///
+/// impl Injectable for Metrics {}
+///
+/// let container = ContainerBuilder::new().build();
+///
+/// let metrics: Option<Metrics> = container.inject()?;
+/// assert!(metrics.is_none());
+///
+/// container.register(Metrics)?;
+///
+/// let metrics: Option<Metrics> = container.inject()?;
+/// assert!(metrics.is_some());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+impl<T> Injectable for Option<T>
+where
+    T: Injectable + Clone,
+{
+    fn inject(container: &Container) -> Result<Option<T>> {
+        if container.has::<Option<T>>() {
+            return container.get();
         }
 
-        self.get()
+        optional(T::inject(container))
+    }
+}
+
+/// Weak references, for breaking cycles.
+///
+/// The container keeps the Rc<T>, building it if needed. Inside a cycle
+/// like A -> Weak<B> -> Rc<A>, the Weak<B> is handed out while Rc<B> is
+/// being built, with Rc::new_cyclic(), so it can only be upgraded once
+/// Rc<B> is built. The cycle can be resolved starting from either side.
+///
+/// The first time a Weak<B> is needed while Rc<B> is being built, the
+/// Rc<B> being built is thrown away and built again with
+/// Rc::new_cyclic(), so the resolve() of B, and of anything built for it,
+/// runs again. Errors are carried out of Rc::new_cyclic() by unwinding,
+/// so if panics abort, inject a [Lazy<Weak<B>>](struct.Lazy.html)
+/// inside cycles instead.
+///
+/// # Examples
+///
+/// ```
+/// use std::rc::{Rc, Weak};
+/// use kamikaze_di::{Container, ContainerBuilder, InjectAsRc, Injector, Result};
+///
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// struct Parent { child: Rc<Child> }
+/// struct Child { parent: Weak<Parent> }
+///
+/// impl InjectAsRc for Parent {
+///     fn resolve(container: &Container) -> Result<Self> {
+///         Ok(Parent { child: container.inject()? })
+///     }
+/// }
+///
+/// impl InjectAsRc for Child {
+///     fn resolve(container: &Container) -> Result<Self> {
+///         Ok(Child { parent: container.inject()? })
+///     }
+/// }
+///
+/// let container = ContainerBuilder::new().build();
+///
+/// let child: Rc<Child> = container.inject()?;
+/// let parent = child.parent.upgrade().unwrap();
+///
+/// assert!(Rc::ptr_eq(&child, &parent.child));
+/// #
+/// # Ok(())
+/// # }
+/// ```
+impl<T> Injectable for Weak<T>
+where
+    T: InjectAsRc + 'static,
+{
+    fn inject(container: &Container) -> Result<Weak<T>> {
+        container.inject_weak()
     }
 }
 
-impl<T> Injector<Rc<T>> for Container
+impl<T> Inject for Rc<T>
 where
     T: InjectAsRc + 'static,
 {
-    fn inject(&self) -> Result<Rc<T>> {
-        debug!("injecting InjectAsRc type");
+    fn resolve(container: &Container) -> Result<Rc<T>> {
+        container.build_rc(T::resolve)
+    }
+
+    fn dependencies() -> Vec<Dependency> {
+        T::dependencies()
+    }
+}
+
+impl<T> Inject for Arc<T>
+where
+    T: InjectAsArc + 'static,
+{
+    fn resolve(container: &Container) -> Result<Arc<T>> {
+        T::resolve(container).map(Arc::new)
+    }
+
+    fn dependencies() -> Vec<Dependency> {
+        T::dependencies()
+    }
+}
+
+/// Lets registered std types be injected, along with Rcs and Arcs of them.
+macro_rules! registered_only {
+    ($($ty:ty),* $(,)*) => {
+        $(
+            impl Injectable for $ty {}
+            impl Injectable for Rc<$ty> {}
+            impl Injectable for Arc<$ty> {}
+        )*
+    };
+}
 
-        if !self.has::<Rc<T>>() {
-            debug!("InjectAsRc type not known, auto-resolving");
+registered_only!(
+    bool,
+    char,
+    f32,
+    f64,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    String,
+    &'static str,
+    Duration,
+    Instant,
+    SystemTime,
+    PathBuf,
+    OsString,
+    IpAddr,
+    Ipv4Addr,
+    Ipv6Addr,
+    SocketAddr,
+    SocketAddrV4,
+    SocketAddrV6
+);
+
+/// Lets registered tuples be injected.
+macro_rules! registered_tuples {
+    ($(($($name:ident),+)),* $(,)*) => {
+        $(
+            impl<$($name: Clone + 'static),+> Injectable for ($($name,)+) {}
+            impl<$($name: 'static),+> Injectable for Rc<($($name,)+)> {}
+            impl<$($name: 'static),+> Injectable for Arc<($($name,)+)> {}
+        )*
+    };
+}
 
-            let item = T::resolve(self)?;
+registered_tuples!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H),
+);
+
+impl<T: Clone + 'static> Injectable for Vec<T> {}
+impl<T: 'static> Injectable for Rc<Vec<T>> {}
+impl<T: 'static> Injectable for Arc<Vec<T>> {}
+impl<T: Clone + 'static> Injectable for VecDeque<T> {}
+impl<T: 'static> Injectable for Rc<VecDeque<T>> {}
+impl<T: 'static> Injectable for Arc<VecDeque<T>> {}
+impl<K: Clone + 'static, V: Clone + 'static> Injectable for HashMap<K, V> {}
+impl<K: 'static, V: 'static> Injectable for Rc<HashMap<K, V>> {}
+impl<K: 'static, V: 'static> Injectable for Arc<HashMap<K, V>> {}
+impl<T: Clone + 'static> Injectable for HashSet<T> {}
+impl<T: 'static> Injectable for Rc<HashSet<T>> {}
+impl<T: 'static> Injectable for Arc<HashSet<T>> {}
+impl<K: Clone + 'static, V: Clone + 'static> Injectable for BTreeMap<K, V> {}
+impl<K: 'static, V: 'static> Injectable for Rc<BTreeMap<K, V>> {}
+impl<K: 'static, V: 'static> Injectable for Arc<BTreeMap<K, V>> {}
+impl<T: Clone + 'static> Injectable for BTreeSet<T> {}
+impl<T: 'static> Injectable for Rc<BTreeSet<T>> {}
+impl<T: 'static> Injectable for Arc<BTreeSet<T>> {}
+
+/// Turns errors about things not being registered into None.
+fn optional<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(item) => Ok(Some(item)),
+        Err(error) if error.is_not_registered() => Ok(None),
+        Err(error) => Err(error),
+    }
+}
 
-            use super::Resolver;
-            let resolver = Resolver::Shared(Box::new(Rc::new(item)));
+/// Used by the derive macros.
+///
+/// Fields that are [Injectable](trait.Injectable.html) get injected,
+/// optional fields of registered types get resolved if they can be,
+/// weak fields get downgraded from registered Rcs, lazy fields get
+/// resolved once the current resolution finishes, other fields get
+/// resolved, so they need to be registered. This picks the right one at
+/// compile time, through auto-deref:
+/// `(&&&FieldInjector::<T>::new()).inject_field(container)` finds
+/// InjectField first, if T is injectable, then ResolveOptionalField,
+/// ResolveWeakField or ResolveLazyField, then ResolveField.
+#[doc(hidden)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FieldInjector<T>(PhantomData<T>);
+
+impl<T> FieldInjector<T> {
+    #[doc(hidden)]
+    pub fn new() -> Self {
+        FieldInjector(PhantomData)
+    }
+}
 
-            self.insert::<Rc<T>>(resolver)?;
+#[doc(hidden)]
+pub trait InjectField<T> {
+    fn inject_field(&self, container: &Container) -> Result<T>;
+}
+
+impl<T: Injectable> InjectField<T> for &&FieldInjector<T> {
+    fn inject_field(&self, container: &Container) -> Result<T> {
+        T::inject(container)
+    }
+}
+
+#[doc(hidden)]
+pub trait ResolveOptionalField<T> {
+    fn inject_field(&self, container: &Container) -> Result<T>;
+}
+
+impl<T: Clone + 'static> ResolveOptionalField<Option<T>> for &FieldInjector<Option<T>> {
+    fn inject_field(&self, container: &Container) -> Result<Option<T>> {
+        if container.has::<Option<T>>() {
+            return container.get();
         }
 
-        self.get()
+        optional(container.get())
     }
 }
+
+#[doc(hidden)]
+pub trait ResolveWeakField<T> {
+    fn inject_field(&self, container: &Container) -> Result<T>;
+}
+
+impl<T: ?Sized + 'static> ResolveWeakField<Weak<T>> for &FieldInjector<Weak<T>> {
+    fn inject_field(&self, container: &Container) -> Result<Weak<T>> {
+        container.resolve_weak()
+    }
+}
+
+#[doc(hidden)]
+pub trait ResolveLazyField<T> {
+    fn inject_field(&self, container: &Container) -> Result<T>;
+}
+
+impl<T: Clone + 'static> ResolveLazyField<Lazy<T>> for &FieldInjector<Lazy<T>> {
+    fn inject_field(&self, container: &Container) -> Result<Lazy<T>> {
+        container.lazy(Container::get)
+    }
+}
+
+#[doc(hidden)]
+pub trait ResolveField<T> {
+    fn inject_field(&self, container: &Container) -> Result<T>;
+}
+
+impl<T: Clone + 'static> ResolveField<T> for FieldInjector<T> {
+    fn inject_field(&self, container: &Container) -> Result<T> {
+        container.get()
+    }
+}
+
+/// Adds the field that could not be resolved to the error.
+#[doc(hidden)]
+pub fn field_error(error: Error, type_name: &str, field: &str) -> Error {
+    error.with_message(format!(
+        "could not resolve {}::{}: {}",
+        type_name, field, error
+    ))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Inject, Injector};
-    use crate::{Container, ContainerBuilder, Result};
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{Inject, InjectAsArc, Injectable, Injector};
+    use crate::{inject, Container, ContainerBuilder, Result};
 
     #[derive(Clone)]
     struct X {
@@ -279,4 +699,132 @@ mod tests {
         let a1_was_not_cloned = Rc::strong_count(&a1.inner) == 3;
         assert!(a1_was_not_cloned);
     }
+
+    #[test]
+    fn registered_only_types_need_registering() {
+        #[derive(Clone)]
+        struct Config;
+        impl Injectable for Config {}
+
+        let container = ContainerBuilder::new().build();
+        let config: Result<Config> = container.inject();
+        let number: Result<Rc<u32>> = container.inject();
+
+        assert!(config.is_err());
+        assert!(number.is_err());
+
+        container.register(Config).unwrap();
+        container.register(Rc::new(42u32)).unwrap();
+
+        let _config: Config = container.inject().unwrap();
+        let number: Rc<u32> = container.inject().unwrap();
+        assert_eq!(42, *number);
+    }
+
+    #[test]
+    fn optional_dependencies_are_none_when_not_registered() {
+        #[derive(Clone, Debug)]
+        struct Config;
+        impl Injectable for Config {}
+
+        #[derive(Clone, Debug)]
+        struct Client;
+        impl Inject for Client {
+            fn resolve(container: &Container) -> Result<Self> {
+                let _: Config = container.inject()?;
+
+                Ok(Client)
+            }
+        }
+
+        #[derive(Clone, Debug)]
+        struct Broken;
+        impl Inject for Broken {
+            fn resolve(_: &Container) -> Result<Self> {
+                Err("could not connect".into())
+            }
+        }
+
+        let container = ContainerBuilder::new().build();
+
+        let client: Option<Client> = container.inject().unwrap();
+        assert!(client.is_none());
+
+        let broken: Result<Option<Broken>> = container.inject();
+        assert_eq!("could not connect", broken.unwrap_err().to_string());
+
+        container.register(Config).unwrap();
+
+        let client: Option<Client> = container.inject().unwrap();
+        assert!(client.is_some());
+    }
+
+    #[test]
+    fn arcs_get_stored() {
+        struct Pool;
+        impl InjectAsArc for Pool {
+            fn resolve(_: &Container) -> Result<Pool> {
+                Ok(Pool)
+            }
+        }
+
+        let container = ContainerBuilder::new().build();
+
+        let pool: Arc<Pool> = container.inject().unwrap();
+        let same_pool: Arc<Pool> = container.inject().unwrap();
+
+        assert!(Arc::ptr_eq(&pool, &same_pool));
+        // one more is kept by the container
+        assert_eq!(3, Arc::strong_count(&pool));
+    }
+
+    trait Voice {
+        fn say(&self) -> String;
+    }
+
+    struct Quiet;
+
+    impl Voice for Quiet {
+        fn say(&self) -> String {
+            "...".to_string()
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Foreign(u8);
+
+    #[test]
+    fn registered_std_types_and_tuples_can_be_injected() {
+        let mut builder = ContainerBuilder::new();
+        builder.register(Duration::from_secs(5)).unwrap();
+        builder.register::<(u8, u8)>((1, 2)).unwrap();
+
+        let container = builder.build();
+
+        let timeout: Duration = container.inject().unwrap();
+        let pair: (u8, u8) = container.inject().unwrap();
+
+        assert_eq!(Duration::from_secs(5), timeout);
+        assert_eq!((1, 2), pair);
+    }
+
+    #[test]
+    fn any_registered_type_can_be_injected_with_the_macro() {
+        let mut builder = ContainerBuilder::new();
+        builder.register::<Rc<dyn Voice>>(Rc::new(Quiet)).unwrap();
+        builder.register(Foreign(7)).unwrap();
+
+        let container = builder.build();
+
+        let voice = inject!(container, Rc<dyn Voice>).unwrap();
+        let foreign = inject!(container, Foreign).unwrap();
+        let y = inject!(container, Y).unwrap();
+
+        assert_eq!("...", voice.say());
+        assert_eq!(Foreign(7), foreign);
+        assert_eq!(42, y.x.inner);
+        assert!(inject!(container, (u8, u8))
+            .unwrap_err()
+            .is_not_registered());
+    }
 }
@@ -0,0 +1,327 @@
+use std::any::{type_name, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::rc::Rc;
+
+use super::observer::CacheStatus;
+use super::{Container, Resolver, ResolverKind};
+use crate::{Error, Result};
+
+/// Keyed builders are stored under this type.
+///
+/// Shows up as "fn(&K) -> T" in registrations and the dependency graph.
+pub(super) type KeyedBy<K, T> = fn(&K) -> T;
+
+/// Builders called by resolve_keyed(), once per key.
+type KeyedBuilder<K, T> = dyn FnMut(&Container, &K) -> T;
+
+/// Items built by a keyed builder, see Resolver::Keyed.
+pub(super) trait KeyedItems: fmt::Debug {
+    /// How many items are cached.
+    fn cached(&self) -> usize;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// The builder and its cache are in separate cells, so items can be
+/// evicted while the builder runs.
+struct Keyed<K, T> {
+    builder: RefCell<Box<KeyedBuilder<K, T>>>,
+    items: RefCell<HashMap<K, T>>,
+}
+
+impl<K, T> KeyedItems for Keyed<K, T>
+where
+    K: Eq + Hash + 'static,
+    T: 'static,
+{
+    fn cached(&self) -> usize {
+        self.items.borrow().len()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<K, T> fmt::Debug for Keyed<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Keyed({} items)", self.items.borrow().len())
+    }
+}
+
+impl Resolver {
+    pub(super) fn keyed<K, T, B>(builder: B) -> Resolver
+    where
+        B: (FnMut(&Container, &K) -> T) + 'static,
+        K: Eq + Hash + 'static,
+        T: 'static,
+    {
+        Resolver::Keyed(Rc::new(Keyed {
+            builder: RefCell::new(Box::new(builder)),
+            items: RefCell::new(HashMap::new()),
+        }))
+    }
+}
+
+impl Container {
+    /// Resolves the item built for key.
+    ///
+    /// The keyed builder registered with
+    /// [register_keyed_builder()](struct.ContainerBuilder.html#method.register_keyed_builder)
+    /// is called the first time a key is used. After that, the item is
+    /// cloned, until it's evicted.
+    ///
+    /// A keyed builder can't resolve its own type, not even for a
+    /// different key, that counts as a circular dependency.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::rc::Rc;
+    /// # use kamikaze_di::Container;
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// struct Pool { database: String }
+    ///
+    /// let container = Container::new();
+    /// container.register_keyed_builder::<String, Rc<Pool>, _>(|_, database| {
+    ///     Rc::new(Pool { database: database.clone() })
+    /// })?;
+    ///
+    /// let users = container.resolve_keyed::<Rc<Pool>, _>(&"users".to_string())?;
+    /// let same_users = container.resolve_keyed::<Rc<Pool>, _>(&"users".to_string())?;
+    /// let orders = container.resolve_keyed::<Rc<Pool>, _>(&"orders".to_string())?;
+    ///
+    /// assert!(Rc::ptr_eq(&users, &same_users));
+    /// assert_eq!("orders", orders.database);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_keyed<T, K>(&self, key: &K) -> Result<T>
+    where
+        T: Clone + 'static,
+        K: Eq + Hash + Clone + 'static,
+    {
+        let type_id = TypeId::of::<KeyedBy<K, T>>();
+        let keyed = self.keyed_items(type_id);
+        let kind = keyed.as_ref().map(|_| ResolverKind::Keyed);
+        let cache = match &keyed {
+            Some(keyed) if downcast::<K, T>(keyed).items.borrow().contains_key(key) => {
+                CacheStatus::Hit
+            }
+            _ => CacheStatus::Miss,
+        };
+
+        let item = self.resolving::<KeyedBy<K, T>, _, _>(kind, cache, || {
+            let _guard = self.cycle_stopper.track(type_id);
+
+            match &keyed {
+                Some(keyed) => Ok(build_keyed(self, downcast(keyed), key)),
+                None => Err(not_keyed::<K, T>()),
+            }
+        })?;
+
+        if let Some(entry) = self.entries.borrow().get(&type_id) {
+            entry.resolutions.set(entry.resolutions.get() + 1);
+        }
+
+        Ok(item)
+    }
+
+    /// Lists the keys that have an item built for them, in no particular order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::Container;
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let container = Container::new();
+    /// container.register_keyed_builder::<u8, String, _>(|_, key| key.to_string())?;
+    ///
+    /// container.resolve_keyed::<String, _>(&1u8)?;
+    ///
+    /// assert_eq!(vec![1], container.cached_keys::<String, u8>()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cached_keys<T, K>(&self) -> Result<Vec<K>>
+    where
+        T: 'static,
+        K: Clone + 'static,
+    {
+        self.with_keyed::<K, T, _, _>(|keyed| keyed.items.borrow().keys().cloned().collect())
+    }
+
+    /// Drops the item built for key, returning it.
+    ///
+    /// The next resolve_keyed() for key calls the builder again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::Container;
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let container = Container::new();
+    /// container.register_keyed_builder::<u8, String, _>(|_, key| key.to_string())?;
+    ///
+    /// container.resolve_keyed::<String, _>(&1u8)?;
+    ///
+    /// assert_eq!(Some("1".to_string()), container.evict::<String, _>(&1u8)?);
+    /// assert_eq!(None, container.evict::<String, _>(&1u8)?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn evict<T, K>(&self, key: &K) -> Result<Option<T>>
+    where
+        T: 'static,
+        K: Eq + Hash + 'static,
+    {
+        // bind the evicted item, so it gets dropped after the borrow ends
+        let evicted =
+            self.with_keyed::<K, T, _, _>(|keyed| keyed.items.borrow_mut().remove(key))?;
+
+        Ok(evicted)
+    }
+
+    /// Drops all items built by the keyed builder for T, returning how many there were.
+    pub fn evict_all<T, K>(&self) -> Result<usize>
+    where
+        T: 'static,
+        K: 'static,
+    {
+        let evicted = self.with_keyed::<K, T, _, _>(|keyed| keyed.items.replace(HashMap::new()))?;
+
+        Ok(evicted.len())
+    }
+
+    fn with_keyed<K, T, R, F>(&self, f: F) -> Result<R>
+    where
+        K: 'static,
+        T: 'static,
+        F: FnOnce(&Keyed<K, T>) -> R,
+    {
+        match self.keyed_items(TypeId::of::<KeyedBy<K, T>>()) {
+            Some(keyed) => Ok(f(downcast(&keyed))),
+            None => Err(not_keyed::<K, T>()),
+        }
+    }
+
+    fn keyed_items(&self, type_id: TypeId) -> Option<Rc<dyn KeyedItems>> {
+        match &self.entries.borrow().get(&type_id)?.resolver {
+            Resolver::Keyed(keyed) => Some(keyed.clone()),
+            _ => None,
+        }
+    }
+}
+
+fn build_keyed<K, T>(container: &Container, keyed: &Keyed<K, T>, key: &K) -> T
+where
+    K: Eq + Hash + Clone,
+    T: Clone,
+{
+    if let Some(item) = keyed.items.borrow().get(key) {
+        return item.clone();
+    }
+
+    let item = (keyed.builder.borrow_mut())(container, key);
+    keyed.items.borrow_mut().insert(key.clone(), item.clone());
+
+    item
+}
+
+fn downcast<K: 'static, T: 'static>(keyed: &Rc<dyn KeyedItems>) -> &Keyed<K, T> {
+    keyed
+        .as_any()
+        .downcast_ref()
+        .expect("could not downcast keyed builder")
+}
+
+fn not_keyed<K, T>() -> Error {
+    Error::not_registered(format!(
+        "No keyed builder registered for {} by {}",
+        type_name::<T>(),
+        type_name::<K>()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::{ContainerBuilder, Resolver, ResolverKind};
+
+    #[test]
+    fn builds_once_per_key() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+
+        let mut builder = ContainerBuilder::new();
+        builder.register::<u32>(10).unwrap();
+        builder
+            .register_keyed_builder::<u32, u32, _>(move |container, key| {
+                counter.set(counter.get() + 1);
+                container.resolve::<u32>().unwrap() + key
+            })
+            .unwrap();
+
+        let container = builder.build();
+
+        assert_eq!(11, container.resolve_keyed::<u32, _>(&1u32).unwrap());
+        assert_eq!(11, container.resolve_keyed::<u32, _>(&1u32).unwrap());
+        assert_eq!(12, container.resolve_keyed::<u32, _>(&2u32).unwrap());
+        assert_eq!(2, calls.get());
+
+        let mut keys = container.cached_keys::<u32, u32>().unwrap();
+        keys.sort();
+        assert_eq!(vec![1, 2], keys);
+
+        assert_eq!(Some(11), container.evict::<u32, _>(&1u32).unwrap());
+        assert_eq!(11, container.resolve_keyed::<u32, _>(&1u32).unwrap());
+        assert_eq!(3, calls.get());
+
+        assert_eq!(2, container.evict_all::<u32, u32>().unwrap());
+        assert!(container.cached_keys::<u32, u32>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn keyed_builders_are_registrations() {
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_keyed_builder::<u8, String, _>(|_, key| key.to_string())
+            .unwrap();
+
+        let container = builder.build();
+
+        let registration = container.registrations()[0];
+        assert_eq!(ResolverKind::Keyed, registration.kind());
+        assert!(!registration.is_built());
+
+        container.resolve_keyed::<String, _>(&1u8).unwrap();
+
+        let registration = container.registrations()[0];
+        assert!(registration.is_built());
+        assert_eq!(1, registration.resolutions());
+    }
+
+    #[test]
+    fn keyed_builders_need_registering() {
+        let container = ContainerBuilder::new().build();
+
+        let error = container.resolve_keyed::<String, _>(&1u8).unwrap_err();
+
+        assert!(error.is_not_registered());
+        assert!(container.evict_all::<String, u8>().is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use std::any::{type_name, TypeId};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use super::injector::Injectable;
+use super::Container;
+use crate::Result;
+
+/// A dependency that becomes available after the current resolution.
+///
+/// Use it for one edge of a dependency cycle that is there on purpose,
+/// like an event bus and its subscribers. When T is not built yet, a
+/// placeholder is injected. T gets resolved, and the placeholder filled,
+/// as soon as the outermost resolution finishes. Using it before that
+/// returns an error.
+///
+/// Cycles without a Lazy in them still panic.
+///
+/// # Examples
+///
+/// ```
+/// use std::rc::Rc;
+/// use kamikaze_di::{Container, ContainerBuilder, InjectAsRc, Injector, Lazy, Result};
+///
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// struct Bus { subscriber: Lazy<Rc<Subscriber>> }
+/// struct Subscriber { bus: Rc<Bus> }
+///
+/// impl InjectAsRc for Bus {
+///     fn resolve(container: &Container) -> Result<Self> {
+///         let subscriber: Lazy<Rc<Subscriber>> = container.inject()?;
+///         // not built yet
+///         assert!(subscriber.get().is_err());
+///
+///         Ok(Bus { subscriber })
+///     }
+/// }
+///
+/// impl InjectAsRc for Subscriber {
+///     fn resolve(container: &Container) -> Result<Self> {
+///         Ok(Subscriber { bus: container.inject()? })
+///     }
+/// }
+///
+/// let container = ContainerBuilder::new().build();
+///
+/// let bus: Rc<Bus> = container.inject()?;
+/// let subscriber = bus.subscriber.get()?;
+///
+/// assert!(Rc::ptr_eq(&bus, &subscriber.bus));
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Lazy<T> {
+    state: Rc<RefCell<State<T>>>,
+}
+
+enum State<T> {
+    Pending,
+    Ready(T),
+    Failed(String),
+}
+
+impl<T: Clone + 'static> Lazy<T> {
+    /// Returns T, if it was built.
+    pub fn get(&self) -> Result<T> {
+        match &*self.state.borrow() {
+            State::Ready(item) => Ok(item.clone()),
+            State::Pending => Err(format!(
+                "{} was used before {} was built, it can only be used after the current resolution finishes",
+                type_name::<Lazy<T>>(),
+                type_name::<T>()
+            )
+            .into()),
+            State::Failed(error) => Err(format!(
+                "{} could not be built: {}",
+                type_name::<T>(),
+                error
+            )
+            .into()),
+        }
+    }
+
+    /// True if T was built.
+    pub fn is_ready(&self) -> bool {
+        match &*self.state.borrow() {
+            State::Ready(_) => true,
+            State::Pending | State::Failed(_) => false,
+        }
+    }
+
+    fn ready(item: T) -> Self {
+        Lazy {
+            state: Rc::new(RefCell::new(State::Ready(item))),
+        }
+    }
+}
+
+impl<T> Clone for Lazy<T> {
+    fn clone(&self) -> Self {
+        Lazy {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match &*self.state.borrow() {
+            State::Pending => "pending",
+            State::Ready(_) => "ready",
+            State::Failed(_) => "failed",
+        };
+
+        write!(f, "Lazy<{}>({})", type_name::<T>(), state)
+    }
+}
+
+impl<T> Injectable for Lazy<T>
+where
+    T: Injectable + Clone,
+{
+    fn inject(container: &Container) -> Result<Lazy<T>> {
+        container.lazy(T::inject)
+    }
+}
+
+/// Fills a placeholder.
+type Fill = dyn FnOnce(&Container);
+
+/// Placeholders waiting for the current resolution to finish.
+#[derive(Default)]
+pub(crate) struct LazyQueue(RefCell<Vec<Box<Fill>>>);
+
+impl LazyQueue {
+    /// Fills all waiting placeholders.
+    pub fn fill(&self, container: &Container) {
+        loop {
+            // bind the next placeholder, so the queue is not borrowed while filling it
+            let next = self.0.borrow_mut().pop();
+
+            match next {
+                Some(fill) => fill(container),
+                None => return,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for LazyQueue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LazyQueue({})", self.0.borrow().len())
+    }
+}
+
+impl Container {
+    /// Lazy<T>, resolved with resolve().
+    pub(crate) fn lazy<T>(&self, resolve: fn(&Container) -> Result<T>) -> Result<Lazy<T>>
+    where
+        T: Clone + 'static,
+    {
+        let built = self.holds_item(TypeId::of::<T>());
+
+        if built || !self.dependencies.is_resolving() {
+            return resolve(self).map(Lazy::ready);
+        }
+
+        let lazy = Lazy {
+            state: Rc::new(RefCell::new(State::Pending)),
+        };
+
+        let state = lazy.state.clone();
+        self.lazy.0.borrow_mut().push(Box::new(move |container| {
+            *state.borrow_mut() = match resolve(container) {
+                Ok(item) => State::Ready(item),
+                Err(error) => State::Failed(error.to_string()),
+            };
+        }));
+
+        Ok(lazy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::Lazy;
+    use crate::{Container, ContainerBuilder, InjectAsRc, Injectable, Injector, Resolver, Result};
+
+    struct Bus {
+        subscriber: Lazy<Rc<Subscriber>>,
+    }
+
+    struct Subscriber {
+        bus: Rc<Bus>,
+    }
+
+    impl InjectAsRc for Bus {
+        fn resolve(container: &Container) -> Result<Self> {
+            Ok(Bus {
+                subscriber: container.inject()?,
+            })
+        }
+    }
+
+    impl InjectAsRc for Subscriber {
+        fn resolve(container: &Container) -> Result<Self> {
+            Ok(Subscriber {
+                bus: container.inject()?,
+            })
+        }
+    }
+
+    #[test]
+    fn fills_placeholders_after_resolving() {
+        let container = ContainerBuilder::new().build();
+
+        let subscriber: Rc<Subscriber> = container.inject().unwrap();
+        let bus: Rc<Bus> = container.inject().unwrap();
+
+        assert!(Rc::ptr_eq(&bus, &subscriber.bus));
+        assert!(Rc::ptr_eq(&subscriber, &bus.subscriber.get().unwrap()));
+    }
+
+    #[test]
+    fn is_ready_right_away_outside_of_resolving() {
+        let container = ContainerBuilder::new().build();
+        container.register::<u32>(42).unwrap();
+
+        let lazy: Lazy<u32> = container.inject().unwrap();
+
+        assert!(lazy.is_ready());
+        assert_eq!(42, lazy.get().unwrap());
+    }
+
+    #[test]
+    fn reports_failures_on_use() {
+        #[derive(Debug, Clone)]
+        struct Config;
+        impl Injectable for Config {}
+
+        let container = ContainerBuilder::new().build();
+        container
+            .register_factory::<Lazy<Config>, _>(|container| {
+                Lazy::<Config>::inject(container).unwrap()
+            })
+            .unwrap();
+
+        let lazy: Lazy<Config> = container.resolve().unwrap();
+
+        assert!(!lazy.is_ready());
+        assert_eq!(
+            format!(
+                "{} could not be built: Type not registered: {}",
+                std::any::type_name::<Config>(),
+                std::any::type_name::<Config>()
+            ),
+            lazy.get().unwrap_err().to_string()
+        );
+    }
+}
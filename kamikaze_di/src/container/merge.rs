@@ -0,0 +1,394 @@
+use std::any::{type_name, TypeId};
+use std::cell::RefCell;
+use std::fmt;
+use std::panic::Location;
+use std::rc::Rc;
+
+use super::{Container, Entry, Resolver, ResolverKind};
+use crate::Result;
+
+/// What [ContainerBuilder::merge()](struct.ContainerBuilder.html#method.merge)
+/// does when both builders registered the same type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConflictPolicy {
+    /// Fail the merge, without merging anything.
+    Error,
+    /// Keep the registration of the builder being merged into.
+    KeepFirst,
+    /// Use the registration of the builder being merged. Only the first
+    /// registration is replaced, bindings added by earlier
+    /// [MultiBinding](enum.ConflictPolicy.html#variant.MultiBinding)
+    /// merges are kept, followed by those of the builder being merged.
+    Replace,
+    /// Keep both. The first registration is resolved as usual, all of
+    /// them are resolved by
+    /// [Container::resolve_all()](struct.Container.html#method.resolve_all).
+    MultiBinding,
+}
+
+/// A type registered by both builders in a merge.
+#[derive(Clone, Copy, Debug)]
+pub struct Conflict {
+    type_name: &'static str,
+    first_registered_at: Option<&'static Location<'static>>,
+    second_registered_at: Option<&'static Location<'static>>,
+}
+
+impl Conflict {
+    /// Name of the type registered twice.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Where the builder being merged into registered the type.
+    pub fn first_registered_at(&self) -> Option<&'static Location<'static>> {
+        self.first_registered_at
+    }
+
+    /// Where the builder being merged registered the type.
+    pub fn second_registered_at(&self) -> Option<&'static Location<'static>> {
+        self.second_registered_at
+    }
+
+    pub(super) fn new(first: &Entry, second: &Entry) -> Conflict {
+        Conflict {
+            type_name: first.type_name,
+            first_registered_at: first.registered_at,
+            second_registered_at: second.registered_at,
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} registered at {} and at {}",
+            self.type_name,
+            location(self.first_registered_at),
+            location(self.second_registered_at)
+        )
+    }
+}
+
+fn location(registered_at: Option<&'static Location<'static>>) -> String {
+    match registered_at {
+        Some(location) => location.to_string(),
+        None => "an unknown location".to_string(),
+    }
+}
+
+impl Entry {
+    /// Merges other into self, following policy.
+    pub(super) fn merge(&mut self, other: Entry, policy: ConflictPolicy) {
+        match policy {
+            ConflictPolicy::Error | ConflictPolicy::KeepFirst => {}
+            ConflictPolicy::Replace => {
+                let mut bindings = std::mem::take(&mut self.bindings);
+
+                *self = other;
+                bindings.append(&mut self.bindings);
+                self.bindings = bindings;
+            }
+            ConflictPolicy::MultiBinding => {
+                let mut other = other;
+                let bindings = std::mem::take(&mut other.bindings);
+
+                self.eager |= other.eager;
+                self.bindings.push(Rc::new(RefCell::new(other)));
+                self.bindings.extend(bindings);
+            }
+        }
+    }
+}
+
+impl Container {
+    /// Resolves every binding of T.
+    ///
+    /// Types get more than one binding when builders are merged with
+    /// [ConflictPolicy::MultiBinding](enum.ConflictPolicy.html#variant.MultiBinding),
+    /// the first one is the one [resolve()](trait.Resolver.html#tymethod.resolve)
+    /// uses. Other types have a single binding. Every binding is resolved
+    /// the way it was registered: builders are built once, per graph
+    /// factories are called once per resolution, and so on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ConflictPolicy, ContainerBuilder};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut core = ContainerBuilder::new();
+    /// core.register::<&str>("core")?;
+    ///
+    /// let mut web = ContainerBuilder::new();
+    /// web.register::<&str>("web")?;
+    ///
+    /// core.merge(&mut web, ConflictPolicy::MultiBinding)?;
+    ///
+    /// let container = core.build();
+    ///
+    /// assert_eq!(vec!["core", "web"], container.resolve_all::<&str>()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_all<T: Clone + 'static>(&self) -> Result<Vec<T>> {
+        let type_id = TypeId::of::<T>();
+        let bindings = match self.entries.borrow().get(&type_id) {
+            Some(entry) => entry.bindings.clone(),
+            None => vec![],
+        };
+
+        let mut items = vec![self.get::<T>()?];
+
+        for (index, binding) in bindings.iter().enumerate() {
+            // per graph items of bindings are kept apart from the first registration's
+            let graph_key = (type_id, index + 1);
+
+            let (kind, holds_item, expiry) = {
+                let entry = binding.borrow();
+
+                (entry.kind, entry.holds_item(), entry.expiry.clone())
+            };
+            let cache = self.entry_cache_status(kind, holds_item, expiry, graph_key);
+
+            let item = self.resolving::<T, _, _>(Some(kind), cache, || {
+                let _guard = self.cycle_stopper.track(type_id);
+
+                self.resolve_binding::<T>(binding, kind, graph_key)
+            })?;
+
+            items.push(item);
+        }
+
+        Ok(items)
+    }
+
+    fn resolve_binding<T: Clone + 'static>(
+        &self,
+        binding: &RefCell<Entry>,
+        kind: ResolverKind,
+        graph_key: (TypeId, usize),
+    ) -> Result<T> {
+        // Nothing is borrowed while factories and builders run, they
+        // can resolve T themselves.
+        let factory = || match &binding.borrow().resolver {
+            Resolver::Factory(cell) => cell.clone(),
+            _ => panic!("Type {} not registered as factory", type_name::<T>()),
+        };
+
+        match kind {
+            ResolverKind::Factory | ResolverKind::Ttl => self.call_factory_in(&factory()),
+            ResolverKind::PerGraph => {
+                self.per_graph_item(graph_key, || self.call_factory_in(&factory()))
+            }
+            ResolverKind::Builder if !binding.borrow().holds_item() => {
+                let pending = match &binding.borrow().resolver {
+                    Resolver::Builder(pending) => pending.clone(),
+                    _ => panic!("Type {} not registered as builder", type_name::<T>()),
+                };
+                let item = self.build_pending::<T>(&pending)?;
+
+                binding.borrow_mut().resolver = Resolver::Shared(item.clone());
+
+                Ok(item
+                    .downcast_ref::<T>()
+                    .expect("could not downcast built object")
+                    .clone())
+            }
+            ResolverKind::Builder
+            | ResolverKind::Shared
+            | ResolverKind::Injected
+            | ResolverKind::PerThread => match &binding.borrow().resolver {
+                Resolver::Shared(item) => Ok(item
+                    .downcast_ref::<T>()
+                    .expect("could not downcast shared object")
+                    .clone()),
+                _ => panic!("Type {} not registered as shared", type_name::<T>()),
+            },
+            ResolverKind::Keyed => Err(format!(
+                "Type {} is registered as a keyed builder, use resolve_keyed()",
+                type_name::<T>()
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::ConflictPolicy;
+    use crate::{ContainerBuilder, Resolver};
+
+    fn core() -> ContainerBuilder {
+        let mut builder = ContainerBuilder::new();
+        builder.register::<u32>(1).unwrap();
+        builder.register::<&str>("core").unwrap();
+
+        builder
+    }
+
+    fn web() -> ContainerBuilder {
+        let mut builder = ContainerBuilder::new();
+        builder.register::<u64>(2).unwrap();
+        builder.register_factory::<&str, _>(|_| "web").unwrap();
+
+        builder
+    }
+
+    #[test]
+    fn errors_list_every_conflict() {
+        let mut builder = core();
+        let mut web = web();
+
+        let error = builder.merge(&mut web, ConflictPolicy::Error).unwrap_err();
+
+        assert!(error.to_string().contains("&str registered at "));
+        assert!(!builder.has::<u64>());
+        assert!(web.has::<u64>());
+
+        builder.merge(&mut web, ConflictPolicy::KeepFirst).unwrap();
+        assert!(builder.has::<u64>());
+        assert!(!web.has::<u64>());
+    }
+
+    #[test]
+    fn reports_conflicts() {
+        let mut builder = core();
+
+        let conflicts = builder
+            .merge(&mut web(), ConflictPolicy::KeepFirst)
+            .unwrap();
+
+        assert_eq!(1, conflicts.len());
+        assert_eq!("&str", conflicts[0].type_name());
+        assert_ne!(
+            conflicts[0].first_registered_at(),
+            conflicts[0].second_registered_at()
+        );
+
+        let container = builder.build();
+
+        assert_eq!("core", container.resolve::<&str>().unwrap());
+        assert_eq!(2, container.resolve::<u64>().unwrap());
+    }
+
+    #[test]
+    fn replaces() {
+        let mut builder = core();
+        builder.merge(&mut web(), ConflictPolicy::Replace).unwrap();
+
+        let container = builder.build();
+
+        assert_eq!("web", container.resolve::<&str>().unwrap());
+        assert_eq!(vec!["web"], container.resolve_all::<&str>().unwrap());
+    }
+
+    #[test]
+    fn replacing_keeps_multi_bindings() {
+        let mut jobs = ContainerBuilder::new();
+        jobs.register_builder::<&str, _>(|_| "jobs").unwrap();
+
+        let mut builder = core();
+        builder
+            .merge(&mut jobs, ConflictPolicy::MultiBinding)
+            .unwrap();
+        builder.merge(&mut web(), ConflictPolicy::Replace).unwrap();
+
+        let container = builder.build();
+
+        assert_eq!("web", container.resolve::<&str>().unwrap());
+        assert_eq!(
+            vec!["web", "jobs"],
+            container.resolve_all::<&str>().unwrap()
+        );
+    }
+
+    #[test]
+    fn bindings_whose_builder_panicked_are_errors() {
+        let mut jobs = ContainerBuilder::new();
+        jobs.register_builder::<&str, _>(|_| panic!("no jobs"))
+            .unwrap();
+
+        let mut builder = core();
+        builder
+            .merge(&mut jobs, ConflictPolicy::MultiBinding)
+            .unwrap();
+
+        let container = builder.build();
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| container.resolve_all::<&str>()));
+
+        assert!(panicked.is_err());
+        assert!(container.resolve_all::<&str>().is_err());
+        assert_eq!("core", container.resolve::<&str>().unwrap());
+    }
+
+    #[test]
+    fn multi_bindings_resolve_all_registrations() {
+        let mut jobs = ContainerBuilder::new();
+        jobs.register_builder::<&str, _>(|_| "jobs").unwrap();
+
+        let mut builder = core();
+        builder
+            .merge(&mut web(), ConflictPolicy::MultiBinding)
+            .unwrap();
+        builder
+            .merge(&mut jobs, ConflictPolicy::MultiBinding)
+            .unwrap();
+
+        let container = builder.build();
+
+        assert_eq!("core", container.resolve::<&str>().unwrap());
+        assert_eq!(
+            vec!["core", "web", "jobs"],
+            container.resolve_all::<&str>().unwrap()
+        );
+        assert_eq!(
+            vec!["core", "web", "jobs"],
+            container.resolve_all::<&str>().unwrap()
+        );
+        assert_eq!(vec![1], container.resolve_all::<u32>().unwrap());
+    }
+
+    #[test]
+    fn multi_bindings_keep_how_they_were_registered() {
+        let builds = Rc::new(Cell::new(0));
+
+        let mut core = ContainerBuilder::new();
+        core.register::<u32>(0).unwrap();
+
+        let mut web = ContainerBuilder::new();
+        let counter = builds.clone();
+        web.register_per_graph::<u32, _>(move |_| {
+            counter.set(counter.get() + 1);
+            counter.get()
+        })
+        .unwrap();
+
+        let mut jobs = ContainerBuilder::new();
+        let token = Cell::new(6);
+        jobs.register_builder_ttl::<u32, _>(Duration::from_secs(3600), move |_| {
+            token.set(token.get() + 1);
+            token.get()
+        })
+        .unwrap();
+
+        core.merge(&mut web, ConflictPolicy::MultiBinding).unwrap();
+        core.merge(&mut jobs, ConflictPolicy::MultiBinding).unwrap();
+
+        let container = core.build();
+
+        assert_eq!(vec![0, 1, 7], container.resolve_all::<u32>().unwrap());
+        assert_eq!(vec![0, 2, 7], container.resolve_all::<u32>().unwrap());
+
+        container.invalidate::<u32>().unwrap();
+        assert_eq!(vec![0, 3, 8], container.resolve_all::<u32>().unwrap());
+    }
+}
@@ -1,15 +1,48 @@
+pub mod assisted;
+pub mod blueprint;
 pub mod builder;
+pub mod dynamic;
+pub mod fork;
+pub mod graph;
 pub mod injector;
+pub mod keyed;
+pub mod lazy;
+pub mod merge;
+pub mod module;
+pub mod observer;
+pub mod per_graph;
+pub mod per_thread;
+pub mod pool;
+pub mod profile;
+pub mod registration;
 pub mod resolver;
+pub mod shared_ref;
+pub mod ttl;
 
 mod cycle;
+mod weak;
 
-use std::any::{Any, TypeId};
-use std::cell::RefCell;
+use std::any::{type_name, Any, TypeId};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::panic::Location;
+use std::rc::Rc;
+use std::time::Duration;
 
-use crate::Result;
+use crate::{Error, Result};
+use assisted::{AssistedFactory, FactoryWith, WithArgs};
 use cycle::CycleStopper;
+use graph::DependencyRecorder;
+use injector::Inject;
+use keyed::{KeyedBy, KeyedItems};
+use lazy::LazyQueue;
+use observer::{CacheStatus, Observers};
+use per_graph::GraphItems;
+use profile::SkippedRegistration;
+use ttl::Expiry;
+use weak::WeakSlots;
 
 /// Dependency container. Can be used with Resolver or Injector.
 ///
@@ -17,8 +50,14 @@ use cycle::CycleStopper;
 /// Use the [ContainerBuilder](struct.ContainerBuilder.html) to set up containers.
 #[derive(Debug)]
 pub struct Container {
-    resolvers: RefCell<HashMap<TypeId, Resolver>>,
+    entries: RefCell<HashMap<TypeId, Entry>>,
     cycle_stopper: CycleStopper,
+    observers: Observers,
+    dependencies: DependencyRecorder,
+    lazy: LazyQueue,
+    skipped: Vec<SkippedRegistration>,
+    graph_items: GraphItems,
+    weak: WeakSlots,
 }
 
 // TODO these can be trait aliases, once that feature becomes stable
@@ -27,6 +66,19 @@ pub type Factory<T> = dyn FnMut(&Container) -> T;
 /// Builders will only be called once
 pub type Builder<T> = dyn FnOnce(&Container) -> T;
 
+/// Makes a new resolver, and its item cache if any, from a copy of a
+/// registered factory, see Entry::forkable().
+#[derive(Clone)]
+struct Forker(Rc<dyn Fn() -> Forked>);
+
+type Forked = (Resolver, Option<Rc<dyn Expiry>>);
+
+impl fmt::Debug for Forker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Forker")
+    }
+}
+
 impl Container {
     /// Creates an empty container.
     ///
@@ -60,58 +112,578 @@ impl Container {
     /// ```
     pub fn new() -> Container {
         Container {
-            resolvers: RefCell::new(Default::default()),
+            entries: RefCell::new(Default::default()),
             cycle_stopper: Default::default(),
+            observers: Default::default(),
+            dependencies: Default::default(),
+            lazy: Default::default(),
+            skipped: vec![],
+            graph_items: Default::default(),
+            weak: Default::default(),
         }
     }
 
-    fn has<T: 'static>(&self) -> bool {
-        debug!("has called");
+    /// Registers a dependency on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register()](struct.ContainerBuilder.html#method.register).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{Container, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let container = Container::new();
+    /// container.register::<u32>(42)?;
+    ///
+    /// assert_eq!(42, container.resolve::<u32>()?);
+    /// assert!(container.register::<u32>(43).is_err());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register<T: 'static>(&self, item: T) -> Result<()> {
+        self.insert::<T>(Entry::registered::<T>(Resolver::shared(item)))
+    }
 
-        let type_id = TypeId::of::<T>();
+    /// Registers a factory on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_factory()](struct.ContainerBuilder.html#method.register_factory).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{Container, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let container = Container::new();
+    /// container.register::<i16>(41)?;
+    /// container.register_factory::<i32, _>(|container| {
+    ///     let base: i16 = container.resolve().unwrap();
+    ///     i32::from(base) + 1
+    /// })?;
+    ///
+    /// assert_eq!(42, container.resolve::<i32>()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_factory<T, F>(&self, factory: F) -> Result<()>
+    where
+        F: (FnMut(&Container) -> T) + Clone + 'static,
+        T: 'static,
+    {
+        self.insert::<T>(Entry::factory::<T, F>(factory))
+    }
+
+    /// Registers an automatic factory on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_automatic_factory()](struct.ContainerBuilder.html#method.register_automatic_factory).
+    #[track_caller]
+    pub fn register_automatic_factory<T: Inject + 'static>(&self) -> Result<()> {
+        self.register_factory(builder::auto_factory::<T>)?;
+        self.dependencies.declare::<T>(&T::dependencies());
+
+        Ok(())
+    }
 
-        self.resolvers.borrow().contains_key(&type_id)
+    /// Registers a per graph factory on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_per_graph()](struct.ContainerBuilder.html#method.register_per_graph).
+    #[track_caller]
+    pub fn register_per_graph<T, F>(&self, factory: F) -> Result<()>
+    where
+        F: (FnMut(&Container) -> T) + Clone + 'static,
+        T: 'static,
+    {
+        self.insert::<T>(Entry::per_graph::<T, F>(factory))
     }
 
-    fn get<T: Clone + 'static>(&self) -> Result<T> {
-        debug!("resolving type via .get()");
+    /// Registers a per thread factory on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_per_thread()](struct.ContainerBuilder.html#method.register_per_thread).
+    #[track_caller]
+    pub fn register_per_thread<T, F>(&self, factory: F) -> Result<()>
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+        T: 'static,
+    {
+        let resolver = Resolver::shared(per_thread::PerThread::new(factory));
 
+        self.insert::<per_thread::PerThread<T>>(Entry::per_thread::<T>(resolver))
+    }
+
+    /// Registers a pool on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_pool()](struct.ContainerBuilder.html#method.register_pool).
+    #[track_caller]
+    pub fn register_pool<T, F>(&self, size: usize, factory: F) -> Result<()>
+    where
+        F: (FnMut(&Container) -> T) + 'static,
+        T: Send + 'static,
+    {
+        self.insert::<pool::Pool<T>>(Entry::pool(size, factory, None)?)
+    }
+
+    /// Registers a factory taking arguments on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_factory_with()](struct.ContainerBuilder.html#method.register_factory_with).
+    #[track_caller]
+    pub fn register_factory_with<Args, T, F>(&self, mut factory: F) -> Result<()>
+    where
+        F: (FnMut(&Container, Args) -> T) + Clone + 'static,
+        Args: 'static,
+        T: 'static,
+    {
+        let factory = move |container: &Container, args| Ok(factory(container, args));
+
+        self.insert::<WithArgs<Args, T>>(Entry::factory_with(factory))
+    }
+
+    /// Registers a keyed builder on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_keyed_builder()](struct.ContainerBuilder.html#method.register_keyed_builder).
+    #[track_caller]
+    pub fn register_keyed_builder<K, T, B>(&self, builder: B) -> Result<()>
+    where
+        B: (FnMut(&Container, &K) -> T) + Clone + 'static,
+        K: Eq + Hash + 'static,
+        T: 'static,
+    {
+        self.insert::<KeyedBy<K, T>>(Entry::keyed::<K, T, B>(builder))
+    }
+
+    /// Registers an assisted factory on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_assisted_factory()](struct.ContainerBuilder.html#method.register_assisted_factory).
+    #[track_caller]
+    pub fn register_assisted_factory<T: AssistedFactory>(&self) -> Result<()> {
+        self.insert::<WithArgs<T::Args, T>>(Entry::factory_with(T::create))?;
+        self.dependencies
+            .declare::<WithArgs<T::Args, T>>(&T::dependencies());
+
+        Ok(())
+    }
+
+    /// Registers a builder on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_builder()](struct.ContainerBuilder.html#method.register_builder).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{Container, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let container = Container::new();
+    /// container.register_builder::<String, _>(|_| "built once".to_string())?;
+    ///
+    /// assert_eq!("built once", container.resolve::<String>()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn register_builder<T, B>(&self, builder: B) -> Result<()>
+    where
+        B: (FnOnce(&Container) -> T) + 'static,
+        T: 'static,
+    {
+        self.insert::<T>(Entry::registered::<T>(Resolver::builder(builder)))
+    }
+
+    /// Registers a builder with a time to live on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_builder_ttl()](struct.ContainerBuilder.html#method.register_builder_ttl).
+    #[track_caller]
+    pub fn register_builder_ttl<T, B>(&self, ttl: Duration, builder: B) -> Result<()>
+    where
+        B: (FnMut(&Container) -> T) + Clone + 'static,
+        T: Clone + 'static,
+    {
+        self.insert::<T>(Entry::ttl::<T, B>(ttl, builder))
+    }
+
+    /// Gives a type a name on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_name()](struct.ContainerBuilder.html#method.register_name).
+    pub fn register_name<T: 'static>(&self, name: impl Into<String>) -> Result<()> {
+        dynamic::name_entry(
+            &mut self.entries.borrow_mut(),
+            TypeId::of::<T>(),
+            type_name::<T>(),
+            name.into(),
+        )
+    }
+
+    /// Registers a dependency that does not implement Clone on an already built container.
+    ///
+    /// Works like [ContainerBuilder::register_shared()](struct.ContainerBuilder.html#method.register_shared).
+    #[track_caller]
+    pub fn register_shared<T: 'static>(&self, item: T) -> Result<()> {
+        self.register::<Rc<T>>(Rc::new(item))
+    }
+
+    /// Registers a builder for a dependency that does not implement Clone on an already
+    /// built container.
+    ///
+    /// Works like [ContainerBuilder::register_shared_builder()](struct.ContainerBuilder.html#method.register_shared_builder).
+    #[track_caller]
+    pub fn register_shared_builder<T, B>(&self, builder: B) -> Result<()>
+    where
+        B: (FnOnce(&Container) -> T) + 'static,
+        T: 'static,
+    {
+        self.register_builder::<Rc<T>, _>(move |container| Rc::new(builder(container)))
+    }
+
+    /// Replaces a registered dependency with a new item.
+    ///
+    /// Whatever was registered for T (item, factory or builder) is dropped
+    /// and T is resolved as the new item from now on.
+    ///
+    /// Items that were resolved before keep their copy of the old value.
+    /// This includes types resolved through [Inject](trait.Inject.html) and
+    /// [InjectAsRc](trait.InjectAsRc.html), since the container stores them after
+    /// they are first resolved. [remove()](struct.Container.html#method.remove)
+    /// them if you want them to be resolved again with the new value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{Container, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let container = Container::new();
+    /// container.register::<&str>("old")?;
+    ///
+    /// let old = container.resolve::<&str>()?;
+    /// container.replace::<&str>("new")?;
+    ///
+    /// assert_eq!("old", old);
+    /// assert_eq!("new", container.resolve::<&str>()?);
+    /// assert!(container.replace::<u8>(1).is_err());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn replace<T: 'static>(&self, item: T) -> Result<()> {
+        self.remove::<T>()?;
+
+        self.register(item)
+    }
+
+    /// Removes a dependency from the container.
+    ///
+    /// The same rules as for [replace()](struct.Container.html#method.replace) apply
+    /// to items that were already resolved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{Container, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let container = Container::new();
+    /// container.register::<u32>(42)?;
+    /// container.remove::<u32>()?;
+    ///
+    /// assert!(!container.has::<u32>());
+    /// assert!(container.remove::<u32>().is_err());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn remove<T: 'static>(&self) -> Result<()> {
         let type_id = TypeId::of::<T>();
-        let _guard = self.cycle_stopper.track(type_id);
 
-        let resolver_type = self.get_resolver_type(type_id);
-        debug!("resolving via {:?}", resolver_type);
+        // bind the removed entry, so it gets dropped after the borrow ends
+        let removed = self.entries.borrow_mut().remove(&type_id);
+
+        match removed {
+            Some(_) => Ok(()),
+            None => Err(Error::not_registered(format!(
+                "Type not registered: {}",
+                type_name::<T>()
+            ))),
+        }
+    }
+
+    fn has<T: 'static>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+
+        self.entries.borrow().contains_key(&type_id)
+    }
+
+    fn get<T: Clone + 'static>(&self) -> Result<T> {
+        let type_id = TypeId::of::<T>();
 
-        match resolver_type {
-            Some(ResolverType::Factory) => self.call_factory::<T>(type_id),
-            Some(ResolverType::Builder) => {
+        self.tracked::<T, _, _>(|kind| match kind {
+            Some(ResolverKind::Factory) | Some(ResolverKind::Ttl) => {
+                self.call_factory::<T>(type_id)
+            }
+            Some(ResolverKind::PerGraph) => self.call_per_graph::<T>(type_id),
+            Some(ResolverKind::Builder) => {
                 self.consume_builder::<T>()?;
                 self.get_shared(type_id)
             }
-            Some(ResolverType::Shared) => self.get_shared(type_id),
-            None => Err(format!("Type not registered: {:?}", type_id).into()),
+            Some(ResolverKind::Shared)
+            | Some(ResolverKind::Injected)
+            | Some(ResolverKind::PerThread) => self.get_shared(type_id),
+            Some(ResolverKind::Keyed) => Err(format!(
+                "Type {} is registered as a keyed builder, use resolve_keyed()",
+                type_name::<T>()
+            )
+            .into()),
+            None => Err(self.not_registered::<T>()),
+        })
+    }
+
+    /// Like get(), but does not clone. Factories can't be used.
+    fn get_ref<T: 'static>(&self) -> Result<Rc<T>> {
+        let type_id = TypeId::of::<T>();
+
+        let item = self.tracked::<T, _, _>(|kind| match kind {
+            Some(ResolverKind::Builder) => {
+                self.consume_builder::<T>()?;
+                Ok(self.shared_item(type_id))
+            }
+            Some(ResolverKind::Shared)
+            | Some(ResolverKind::Injected)
+            | Some(ResolverKind::PerThread) => Ok(self.shared_item(type_id)),
+            Some(ResolverKind::Factory)
+            | Some(ResolverKind::PerGraph)
+            | Some(ResolverKind::Ttl)
+            | Some(ResolverKind::Keyed) => Err(format!(
+                "Type {} is registered as a factory, it can't be borrowed",
+                type_name::<T>()
+            )
+            .into()),
+            None => Err(self.not_registered::<T>()),
+        })?;
+
+        Ok(item.downcast().expect("could not downcast shared object"))
+    }
+
+    /// Error for types that are not registered, with hints for when
+    /// T was confused with Rc<T>.
+    fn not_registered<T: 'static>(&self) -> Error {
+        let name = type_name::<T>();
+        let entries = self.entries.borrow();
+
+        if entries.contains_key(&TypeId::of::<Rc<T>>()) {
+            return Error::not_registered(format!(
+                "Type not registered: {}, but {} is, resolve that instead",
+                name,
+                type_name::<Rc<T>>()
+            ));
+        }
+
+        let type_id = TypeId::of::<T>();
+        if let Some(entry) = entries.values().find(|entry| entry.rc_type_id == type_id) {
+            return Error::not_registered(format!(
+                "Type not registered: {}, but {} is, resolve that instead or use register_shared()",
+                name, entry.type_name
+            ));
+        }
+
+        Error::not_registered(format!("Type not registered: {}", name))
+    }
+
+    /// Runs resolve() with T's resolver kind, for all the bookkeeping
+    /// that comes with resolving a type.
+    fn tracked<T, R, F>(&self, resolve: F) -> Result<R>
+    where
+        T: 'static,
+        F: FnOnce(Option<ResolverKind>) -> Result<R>,
+    {
+        let type_id = TypeId::of::<T>();
+
+        let kind = self.get_resolver_kind(type_id);
+        let cache = self.cache_status(type_id, kind);
+
+        let item = self.resolving::<T, _, _>(kind, cache, || {
+            let _guard = self.cycle_stopper.track(type_id);
+
+            resolve(kind)
+        })?;
+
+        if let Some(entry) = self.entries.borrow().get(&type_id) {
+            entry.resolutions.set(entry.resolutions.get() + 1);
         }
+
+        Ok(item)
     }
 
-    fn get_resolver_type(&self, type_id: TypeId) -> Option<ResolverType> {
-        self.resolvers.borrow().get(&type_id).map(|r| r.into())
+    /// Resolves a type that is not registered yet, then stores it as shared.
+    fn auto_inject<T, F>(&self, resolve: F) -> Result<T>
+    where
+        T: Clone + 'static,
+        F: FnOnce(&Container) -> Result<T>,
+    {
+        let kind = Some(ResolverKind::Injected);
+
+        self.resolving::<T, _, _>(kind, CacheStatus::Miss, || {
+            let item = resolve(self)?;
+
+            // built meanwhile, by the other side of a Weak<T> cycle
+            if self.has::<T>() {
+                return self.get();
+            }
+
+            let entry = Entry::injected::<T>(Resolver::shared(item.clone()));
+
+            self.insert::<T>(entry)?;
+
+            Ok(item)
+        })
+    }
+
+    /// Runs T's builder, if it was not already called.
+    fn initialize_builder<T: 'static>(&self) -> Result<()> {
+        let type_id = TypeId::of::<T>();
+        let kind = self.get_resolver_kind(type_id);
+
+        if kind != Some(ResolverKind::Builder) || self.holds_item(type_id) {
+            return Ok(());
+        }
+
+        self.resolving::<T, _, _>(kind, CacheStatus::Miss, || {
+            let _guard = self.cycle_stopper.track(type_id);
+
+            self.consume_builder::<T>()
+        })
+    }
+
+    /// Runs resolve() for T, keeping observers and the dependency graph up to date.
+    ///
+    /// Once the outermost resolution finishes, per graph items are dropped
+    /// and lazy placeholders are filled.
+    fn resolving<T, R, F>(
+        &self,
+        kind: Option<ResolverKind>,
+        cache: CacheStatus,
+        resolve: F,
+    ) -> Result<R>
+    where
+        T: 'static,
+        F: FnOnce() -> Result<R>,
+    {
+        let item = self
+            .dependencies
+            .record::<T, _, _>(|| self.observers.observe::<T, _, _>(kind, cache, resolve));
+
+        if !self.dependencies.is_resolving() {
+            self.graph_items.clear();
+            self.lazy.fill(self);
+        }
+
+        item
+    }
+
+    fn get_resolver_kind(&self, type_id: TypeId) -> Option<ResolverKind> {
+        self.entries.borrow().get(&type_id).map(|entry| entry.kind)
+    }
+
+    /// True if the entry of type_id holds its item, like built builders do.
+    fn holds_item(&self, type_id: TypeId) -> bool {
+        match self.entries.borrow().get(&type_id) {
+            Some(entry) => entry.holds_item(),
+            None => false,
+        }
+    }
+
+    /// Whether resolving type_id reuses an item, for observers.
+    fn cache_status(&self, type_id: TypeId, kind: Option<ResolverKind>) -> CacheStatus {
+        let entries = self.entries.borrow();
+
+        match (kind, entries.get(&type_id)) {
+            (Some(kind), Some(entry)) => {
+                let holds_item = entry.holds_item();
+                let expiry = entry.expiry.clone();
+                drop(entries);
+
+                self.entry_cache_status(kind, holds_item, expiry, (type_id, 0))
+            }
+            _ => CacheStatus::Miss,
+        }
+    }
+
+    /// Whether resolving an entry reuses an item, without the entries
+    /// borrowed, since checking the clock of ttl builders can resolve it.
+    fn entry_cache_status(
+        &self,
+        kind: ResolverKind,
+        holds_item: bool,
+        expiry: Option<Rc<dyn Expiry>>,
+        graph_key: (TypeId, usize),
+    ) -> CacheStatus {
+        let hit = match kind {
+            ResolverKind::Shared | ResolverKind::Injected | ResolverKind::PerThread => true,
+            ResolverKind::Builder => holds_item,
+            ResolverKind::PerGraph => self.graph_items.has(graph_key),
+            ResolverKind::Ttl => expiry.is_some_and(|expiry| expiry.is_cached(self)),
+            ResolverKind::Factory | ResolverKind::Keyed => false,
+        };
+
+        if hit {
+            CacheStatus::Hit
+        } else {
+            CacheStatus::Miss
+        }
     }
 
     fn call_factory<T: 'static>(&self, type_id: TypeId) -> Result<T> {
-        if let Resolver::Factory(cell) = self
-            .resolvers
+        self.call_factory_in::<T>(&self.factory(type_id))
+    }
+
+    fn call_factory_in<T: 'static>(&self, cell: &RefCell<Box<dyn Any>>) -> Result<T> {
+        let mut boxed = cell.borrow_mut();
+
+        if let Some(factory) = boxed.downcast_mut::<Box<FactoryWith<(), T>>>() {
+            // builders with a time to live, see Entry::ttl()
+            return factory(self, ());
+        }
+
+        match boxed.downcast_mut::<Box<Factory<T>>>() {
+            Some(factory) => Ok(factory(self)),
+            None => Err(format!(
+                "Type {} is registered as a factory with arguments, use resolve_with()",
+                type_name::<T>()
+            )
+            .into()),
+        }
+    }
+
+    fn call_factory_with<Args: 'static, T: 'static>(&self, args: Args) -> Result<T> {
+        let cell = self.factory(TypeId::of::<WithArgs<Args, T>>());
+        let mut boxed = cell.borrow_mut();
+        let factory = boxed
+            .downcast_mut::<Box<FactoryWith<Args, T>>>()
+            .expect("could not downcast factory");
+
+        factory(self, args)
+    }
+
+    fn factory(&self, type_id: TypeId) -> Rc<RefCell<Box<dyn Any>>> {
+        // The factory is cloned out so the entries are not borrowed while
+        // it runs, this allows factories to register things themselves.
+        if let Resolver::Factory(cell) = &self
+            .entries
             .borrow()
             .get(&type_id)
             .expect("could not find a registered factory")
+            .resolver
         {
-            let mut boxed = cell.borrow_mut();
-            let factory = boxed
-                .downcast_mut::<Box<Factory<T>>>()
-                .expect("could not downcast factory");
-
-            let item = factory(self);
-
-            return Ok(item);
+            return cell.clone();
         }
 
         panic!("Type {:?} not registered as factory", type_id)
@@ -120,55 +692,83 @@ impl Container {
     fn consume_builder<T: 'static>(&self) -> Result<()> {
         let type_id = TypeId::of::<T>();
 
-        let builder = if let Resolver::Builder(boxed) = self
-            .resolvers
-            .borrow_mut()
-            .remove(&type_id)
+        let pending = match &self
+            .entries
+            .borrow()
+            .get(&type_id)
             .expect("could not find a registered resolver")
+            .resolver
         {
-            boxed
-                .downcast::<Box<Builder<T>>>()
-                .expect("could not downcast builder")
-        } else {
-            panic!("Type {:?} not registered as builder", type_id)
+            Resolver::Builder(pending) => pending.clone(),
+            Resolver::Shared(_) => return Ok(()),
+            _ => panic!("Type {:?} not registered as builder", type_id),
         };
 
-        let item = builder(self);
-        let resolver = Resolver::Shared(Box::new(item));
+        let item = self.build_pending::<T>(&pending)?;
 
-        self.insert::<T>(resolver)
+        if let Some(entry) = self.entries.borrow_mut().get_mut(&type_id) {
+            entry.resolver = Resolver::Shared(item);
+        }
+
+        Ok(())
+    }
+
+    /// Calls the builder, or takes what a fork built with it.
+    ///
+    /// Nothing is borrowed while the builder runs, it can resolve other
+    /// things.
+    fn build_pending<T: 'static>(&self, pending: &PendingBuilder) -> Result<Rc<dyn Any>> {
+        if let Some(item) = pending.built.borrow().clone() {
+            return Ok(item);
+        }
+
+        let builder = pending.builder.borrow_mut().take().ok_or_else(|| {
+            format!(
+                "Type {} is being built by a fork, or its builder panicked",
+                type_name::<T>()
+            )
+        })?;
+        let builder = builder
+            .downcast::<Box<Builder<T>>>()
+            .expect("could not downcast builder");
+
+        let item: Rc<dyn Any> = Rc::new(builder(self));
+        *pending.built.borrow_mut() = Some(item.clone());
+
+        Ok(item)
     }
 
     fn get_shared<T: Clone + 'static>(&self, type_id: TypeId) -> Result<T> {
-        if let Resolver::Shared(boxed_any) = self
-            .resolvers
+        let item = self.shared_item(type_id);
+        let item: &T = item
+            .downcast_ref()
+            .expect("could not downcast shared object");
+
+        Ok(item.clone())
+    }
+
+    fn shared_item(&self, type_id: TypeId) -> Rc<dyn Any> {
+        if let Resolver::Shared(item) = &self
+            .entries
             .borrow()
             .get(&type_id)
             .expect("could not find a registered type")
+            .resolver
         {
-            use std::borrow::Borrow;
-
-            let borrowed_any: &dyn Any = boxed_any.borrow();
-            let borrowed_item: &T = borrowed_any
-                .downcast_ref()
-                .expect("could not downcast shared object");
-
-            return Ok(borrowed_item.clone());
+            return item.clone();
         }
 
         panic!("Type {:?} not registered as shared dependency", type_id)
     }
 
-    fn insert<T: 'static>(&self, resolver: Resolver) -> Result<()> {
-        debug!("inerting new type");
-
+    fn insert<T: 'static>(&self, entry: Entry) -> Result<()> {
         let type_id = TypeId::of::<T>();
 
         if self.has::<T>() {
             return Err(format!("Container already has {:?}", type_id).into());
         }
 
-        self.resolvers.borrow_mut().insert(type_id, resolver);
+        self.entries.borrow_mut().insert(type_id, entry);
 
         Ok(())
     }
@@ -180,34 +780,248 @@ impl Default for Container {
     }
 }
 
+/// Something known to the container, together with its metadata.
+#[derive(Debug)]
+struct Entry {
+    resolver: Resolver,
+    type_name: &'static str,
+    kind: ResolverKind,
+    registered_at: Option<&'static Location<'static>>,
+    /// Name of the module that registered the type, if any.
+    module: Option<&'static str>,
+    resolutions: Cell<usize>,
+    /// Used for hints when Rc<T> is resolved instead of T.
+    rc_type_id: TypeId,
+    /// More registrations of T, from merging builders, see resolve_all().
+    bindings: Vec<Rc<RefCell<Entry>>>,
+    /// Runs the builder of T, for builders that were not built yet.
+    initialize: Option<fn(&Container) -> Result<()>>,
+    /// Item cache of builders with a time to live, see invalidate().
+    expiry: Option<Rc<dyn Expiry>>,
+    /// Creates the resolver of forks, for factories, see fork().
+    forker: Option<Forker>,
+    /// Number of objects, for pools, see pool_stats().
+    pool_size: Option<usize>,
+    /// Resolves T without knowing it, see resolve_dyn().
+    dynamic: fn(&Container) -> Result<Rc<dyn Any>>,
+    /// Name for resolve_by_name(), if any.
+    name: Option<String>,
+    /// Built by build_eager(), see ContainerBuilder::eager().
+    eager: bool,
+}
+
+impl Entry {
+    /// True if the entry holds its item, like built builders do.
+    fn holds_item(&self) -> bool {
+        matches!(self.resolver, Resolver::Shared(_))
+    }
+
+    #[track_caller]
+    fn registered<T: 'static>(resolver: Resolver) -> Entry {
+        let initialize: Option<fn(&Container) -> Result<()>> = match resolver {
+            Resolver::Builder(_) => Some(Container::initialize_builder::<T>),
+            _ => None,
+        };
+
+        Entry {
+            kind: (&resolver).into(),
+            resolver,
+            type_name: type_name::<T>(),
+            registered_at: Some(Location::caller()),
+            module: None,
+            resolutions: Cell::new(0),
+            rc_type_id: TypeId::of::<Rc<T>>(),
+            bindings: vec![],
+            initialize,
+            expiry: None,
+            forker: None,
+            pool_size: None,
+            dynamic: Container::get_dyn::<T>,
+            name: None,
+            eager: false,
+        }
+    }
+
+    /// Entry for T, with the resolver and item cache made by forker.
+    ///
+    /// Forks call forker again, so they don't share any state with
+    /// this entry.
+    #[track_caller]
+    fn forkable<T: 'static>(forker: Forker) -> Entry {
+        let (resolver, expiry) = (forker.0)();
+
+        Entry {
+            expiry,
+            forker: Some(forker),
+            ..Entry::registered::<T>(resolver)
+        }
+    }
+
+    #[track_caller]
+    fn factory<T, F>(factory: F) -> Entry
+    where
+        F: (FnMut(&Container) -> T) + Clone + 'static,
+        T: 'static,
+    {
+        Entry::forkable::<T>(Forker(Rc::new(move || {
+            (Resolver::factory(factory.clone()), None)
+        })))
+    }
+
+    #[track_caller]
+    fn factory_with<Args, T, F>(factory: F) -> Entry
+    where
+        F: (FnMut(&Container, Args) -> Result<T>) + Clone + 'static,
+        Args: 'static,
+        T: 'static,
+    {
+        Entry::forkable::<WithArgs<Args, T>>(Forker(Rc::new(move || {
+            (Resolver::factory_with(factory.clone()), None)
+        })))
+    }
+
+    #[track_caller]
+    fn keyed<K, T, B>(builder: B) -> Entry
+    where
+        B: (FnMut(&Container, &K) -> T) + Clone + 'static,
+        K: Eq + Hash + 'static,
+        T: 'static,
+    {
+        Entry::forkable::<KeyedBy<K, T>>(Forker(Rc::new(move || {
+            (Resolver::keyed(builder.clone()), None)
+        })))
+    }
+
+    fn injected<T: 'static>(resolver: Resolver) -> Entry {
+        Entry {
+            resolver,
+            type_name: type_name::<T>(),
+            kind: ResolverKind::Injected,
+            registered_at: None,
+            module: None,
+            resolutions: Cell::new(1),
+            rc_type_id: TypeId::of::<Rc<T>>(),
+            bindings: vec![],
+            initialize: None,
+            expiry: None,
+            forker: None,
+            pool_size: None,
+            dynamic: Container::get_dyn::<T>,
+            name: None,
+            eager: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 enum Resolver {
     /// Factories get called multiple times
     ///
     /// Factories are called by the container, and they themselves will
     /// call container.resolve() as they see fit. This means we can't
-    /// own a mutable borrow to the resolvers collection during the
-    /// calls. Thus we must use RefCell.
-    Factory(RefCell<Box<dyn Any>>),
-    Builder(Box<dyn Any>),
-    Shared(Box<dyn Any>),
+    /// own a mutable borrow to the entries collection during the
+    /// calls. Thus we must use RefCell, inside an Rc so the factory can
+    /// be taken out of the collection while it's being called.
+    Factory(Rc<RefCell<Box<dyn Any>>>),
+    /// Builders are shared with forks, see PendingBuilder.
+    Builder(Rc<PendingBuilder>),
+    /// Shared items are in an Rc, so they can be borrowed without
+    /// borrowing the entries collection.
+    Shared(Rc<dyn Any>),
+    /// Keyed builders hold their own items, one per key. They are in
+    /// an Rc for the same reason factories are.
+    Keyed(Rc<dyn KeyedItems>),
+}
+
+impl Resolver {
+    fn shared<T: 'static>(item: T) -> Resolver {
+        // shared resolvers hold Rc<Any>
+        Resolver::Shared(Rc::new(item))
+    }
+
+    fn factory<T, F>(factory: F) -> Resolver
+    where
+        F: (FnMut(&Container) -> T) + 'static,
+        T: 'static,
+    {
+        // We use double boxes so we can downcast to the inner box type.
+        // you can only downcast to Sized types, that's why we need an inner box
+        // see call_factory() for use.
+        let boxed: Box<Factory<T>> = Box::new(factory);
+        let boxed: Box<dyn Any> = Box::new(boxed);
+
+        Resolver::Factory(Rc::new(RefCell::new(boxed)))
+    }
+
+    fn factory_with<Args, T, F>(factory: F) -> Resolver
+    where
+        F: (FnMut(&Container, Args) -> Result<T>) + 'static,
+        Args: 'static,
+        T: 'static,
+    {
+        // Same double boxing as for factories, see call_factory_with() for use.
+        let boxed: Box<FactoryWith<Args, T>> = Box::new(factory);
+        let boxed: Box<dyn Any> = Box::new(boxed);
+
+        Resolver::Factory(Rc::new(RefCell::new(boxed)))
+    }
+
+    fn builder<T, B>(builder: B) -> Resolver
+    where
+        B: (FnOnce(&Container) -> T) + 'static,
+        T: 'static,
+    {
+        // Same double boxing as for factories, see build_pending() for use.
+        let boxed: Box<Builder<T>> = Box::new(builder);
+        let boxed: Box<dyn Any> = Box::new(boxed);
+
+        Resolver::Builder(Rc::new(PendingBuilder {
+            builder: RefCell::new(Some(boxed)),
+            built: RefCell::new(None),
+        }))
+    }
 }
 
+/// A builder that was not called yet.
+///
+/// A container and its forks share it. The first one to resolve the
+/// type calls it, the others reuse what it built.
 #[derive(Debug)]
-enum ResolverType {
+struct PendingBuilder {
+    builder: RefCell<Option<Box<dyn Any>>>,
+    built: RefCell<Option<Rc<dyn Any>>>,
+}
+
+/// How a type gets resolved by the container.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ResolverKind {
+    /// Registered factory, called on every resolve.
     Factory,
+    /// Registered builder, called on the first resolve only.
     Builder,
+    /// Shared item, cloned on every resolve.
     Shared,
+    /// Not registered, but resolved through the Inject, InjectAsRc or InjectAsArc traits.
+    Injected,
+    /// Registered keyed builder, called on the first resolve of every key.
+    Keyed,
+    /// Registered per graph factory, called once per outermost resolution.
+    PerGraph,
+    /// Registered per thread factory, shared as a handle, called once per thread.
+    PerThread,
+    /// Registered builder with a time to live, called again on the first resolve after expiry.
+    Ttl,
 }
 
-impl From<&Resolver> for ResolverType {
+impl From<&Resolver> for ResolverKind {
     fn from(other: &Resolver) -> Self {
-        use ResolverType::*;
+        use ResolverKind::*;
 
         match other {
             Resolver::Factory(_) => Factory,
             Resolver::Builder(_) => Builder,
             Resolver::Shared(_) => Shared,
+            Resolver::Keyed(_) => Keyed,
         }
     }
 }
@@ -215,7 +1029,8 @@ impl From<&Resolver> for ResolverType {
 #[cfg(test)]
 mod tests {
     use super::builder::ContainerBuilder;
-    use crate::Resolver;
+    use super::Container;
+    use crate::{Dependency, Inject, Injector, Resolver, Result};
 
     #[test]
     #[should_panic(expected = "Circular dependency")]
@@ -244,6 +1059,175 @@ mod tests {
 
         container.resolve::<i32>().unwrap();
     }
+
+    #[test]
+    fn build_eager_calls_builders_in_dependency_order() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut builder = ContainerBuilder::new();
+
+        let log = calls.clone();
+        builder
+            .register_builder::<i64, _>(move |container| {
+                let base: i32 = container.resolve().unwrap();
+                log.borrow_mut().push("i64");
+                base.into()
+            })
+            .unwrap();
+
+        let log = calls.clone();
+        builder
+            .register_builder::<i32, _>(move |_| {
+                log.borrow_mut().push("i32");
+                42
+            })
+            .unwrap();
+
+        let log = calls.clone();
+        builder
+            .register_builder::<u8, _>(move |_| {
+                log.borrow_mut().push("u8");
+                42
+            })
+            .unwrap();
+
+        builder.eager::<i64>().unwrap();
+        assert!(builder.eager::<u16>().unwrap_err().is_not_registered());
+        assert!(builder.registrations()[1].is_eager());
+
+        let container = builder.build_eager().unwrap();
+
+        assert_eq!(vec!["i32", "i64"], *calls.borrow());
+        assert!(!container.registrations()[2].is_built());
+        assert_eq!(42, container.resolve::<i64>().unwrap());
+    }
+
+    #[test]
+    fn build_eager_lists_every_failure() {
+        #[derive(Clone)]
+        struct A;
+        impl Inject for A {
+            fn resolve(_: &Container) -> Result<Self> {
+                Err("A failed".into())
+            }
+        }
+
+        #[derive(Clone)]
+        struct B;
+        impl Inject for B {
+            fn resolve(_: &Container) -> Result<Self> {
+                Err("B failed".into())
+            }
+        }
+
+        let mut builder = ContainerBuilder::new();
+        builder.eager_root::<A>();
+        builder.eager_root::<B>();
+
+        let error: String = builder.build_eager().unwrap_err().into();
+
+        assert!(error.contains("A failed"));
+        assert!(error.contains("B failed"));
+    }
+
+    #[test]
+    fn build_eager_lists_dependencies_first() {
+        #[derive(Clone)]
+        struct Config;
+        impl Inject for Config {
+            fn resolve(_: &Container) -> Result<Self> {
+                Err("no config".into())
+            }
+        }
+
+        #[derive(Clone)]
+        struct Server;
+        impl Inject for Server {
+            fn resolve(container: &Container) -> Result<Self> {
+                let _: Config = container.inject()?;
+
+                Ok(Server)
+            }
+
+            fn dependencies() -> Vec<Dependency> {
+                vec![Dependency::of::<Config>()]
+            }
+        }
+
+        let mut builder = ContainerBuilder::new();
+        builder.register::<u8>(1).unwrap();
+        builder.eager_root::<Server>();
+        builder.eager_root::<Config>();
+
+        assert!(builder.eager::<u8>().is_err());
+
+        let error: String = builder.build_eager().unwrap_err().into();
+        let config = error.find("Config: no config").unwrap();
+        let server = error.find("Server: no config").unwrap();
+
+        assert!(config < server);
+    }
+
+    #[test]
+    fn factories_can_register_dependencies() {
+        #[derive(Clone)]
+        struct X;
+        impl Inject for X {
+            fn resolve(_: &Container) -> Result<Self> {
+                Ok(X)
+            }
+        }
+
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_factory::<i32, _>(|container| {
+                use crate::Injector;
+
+                let _x: X = container.inject().unwrap();
+                container.register::<u8>(1).unwrap();
+
+                42
+            })
+            .unwrap();
+
+        let container = builder.build();
+
+        assert_eq!(42, container.resolve::<i32>().unwrap());
+        assert!(container.has::<X>());
+        assert!(container.has::<u8>());
+    }
+
+    #[test]
+    fn hints_when_confusing_t_and_rc_t() {
+        use std::rc::Rc;
+
+        let container = Container::new();
+        container.register::<u8>(1).unwrap();
+        container.register_shared::<u16>(2).unwrap();
+
+        let error: String = container.resolve::<Rc<u8>>().unwrap_err().into();
+        assert!(error.contains("but u8 is"));
+
+        let error: String = container.resolve::<u16>().unwrap_err().into();
+        assert!(error.contains("but alloc::rc::Rc<u16> is"));
+
+        let error: String = container.resolve::<u32>().unwrap_err().into();
+        assert_eq!("Type not registered: u32", error);
+    }
+
+    #[test]
+    fn replaced_factories_are_not_called() {
+        let container = Container::new();
+        container
+            .register_factory::<i32, _>(|_| panic!("replaced factory called"))
+            .unwrap();
+
+        container.replace::<i32>(42).unwrap();
+
+        assert_eq!(42, container.resolve::<i32>().unwrap());
+    }
 }
 
 // Prevent users from implementing Injector and Resolver
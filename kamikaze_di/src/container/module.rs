@@ -0,0 +1,175 @@
+use std::any::type_name;
+
+use super::builder::ContainerBuilder;
+use crate::Result;
+
+/// A package of registrations.
+///
+/// Install it with [ContainerBuilder::install()](struct.ContainerBuilder.html#method.install).
+/// Modules can require other modules, those get installed first.
+///
+/// Modules are identified by [name()](trait.Module.html#method.name),
+/// installing a module that is already installed does nothing.
+///
+/// # Examples
+///
+/// ```
+/// use kamikaze_di::{ContainerBuilder, Module, Resolver, Result};
+///
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// struct Config;
+///
+/// impl Module for Config {
+///     fn configure(&self, builder: &mut ContainerBuilder) -> Result<()> {
+///         builder.register::<String>("localhost".to_string())
+///     }
+/// }
+///
+/// struct Database;
+///
+/// impl Module for Database {
+///     fn configure(&self, builder: &mut ContainerBuilder) -> Result<()> {
+///         builder.register_factory::<u16, _>(|_| 3306)
+///     }
+///
+///     fn requires(&self) -> Vec<Box<dyn Module>> {
+///         vec![Box::new(Config)]
+///     }
+/// }
+///
+/// let mut builder = ContainerBuilder::new();
+/// builder.install(Database)?;
+/// builder.install(Config)?;
+///
+/// let string = builder.registrations()[0];
+/// assert!(string.module().unwrap().ends_with("Config"));
+///
+/// let container = builder.build();
+///
+/// assert_eq!("localhost", container.resolve::<String>()?);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub trait Module {
+    /// Registers everything the module provides.
+    fn configure(&self, builder: &mut ContainerBuilder) -> Result<()>;
+
+    /// Lists the modules this module needs.
+    fn requires(&self) -> Vec<Box<dyn Module>> {
+        vec![]
+    }
+
+    /// Identifies the module, defaults to its type name.
+    fn name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Module;
+    use crate::{ContainerBuilder, Resolver, Result};
+
+    struct Core;
+
+    impl Module for Core {
+        fn configure(&self, builder: &mut ContainerBuilder) -> Result<()> {
+            builder.register::<u32>(42)
+        }
+    }
+
+    struct Web;
+
+    impl Module for Web {
+        fn configure(&self, builder: &mut ContainerBuilder) -> Result<()> {
+            builder.register_factory::<String, _>(|container| {
+                let port: u32 = container.resolve().unwrap();
+                format!("localhost:{}", port)
+            })
+        }
+
+        fn requires(&self) -> Vec<Box<dyn Module>> {
+            vec![Box::new(Core)]
+        }
+    }
+
+    struct Jobs;
+
+    impl Module for Jobs {
+        fn configure(&self, builder: &mut ContainerBuilder) -> Result<()> {
+            builder.register::<u64>(7)
+        }
+
+        fn requires(&self) -> Vec<Box<dyn Module>> {
+            vec![Box::new(Core), Box::new(Web)]
+        }
+    }
+
+    #[test]
+    fn installs_required_modules_once() {
+        let mut builder = ContainerBuilder::new();
+        builder.install(Web).unwrap();
+        builder.install(Jobs).unwrap();
+        builder.install(Core).unwrap();
+
+        let modules: Vec<_> = builder
+            .installed_modules()
+            .iter()
+            .map(|name| name.rsplit("::").next().unwrap())
+            .collect();
+        assert_eq!(vec!["Core", "Web", "Jobs"], modules);
+
+        let container = builder.build();
+
+        assert_eq!("localhost:42", container.resolve::<String>().unwrap());
+        assert_eq!(7, container.resolve::<u64>().unwrap());
+    }
+
+    #[test]
+    fn registrations_know_their_module() {
+        let mut builder = ContainerBuilder::new();
+        builder.install(Web).unwrap();
+        builder.register::<u64>(7).unwrap();
+
+        let modules: Vec<_> = builder
+            .registrations()
+            .iter()
+            .map(|registration| (registration.type_name(), registration.module()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("alloc::string::String", Some(std::any::type_name::<Web>())),
+                ("u32", Some(std::any::type_name::<Core>())),
+                ("u64", None),
+            ],
+            modules
+        );
+    }
+
+    #[test]
+    fn errors_name_the_module() {
+        struct Broken;
+
+        impl Module for Broken {
+            fn configure(&self, builder: &mut ContainerBuilder) -> Result<()> {
+                builder.register::<u32>(1)
+            }
+
+            fn name(&self) -> &'static str {
+                "broken"
+            }
+        }
+
+        let mut builder = ContainerBuilder::new();
+        builder.install(Core).unwrap();
+
+        let error = builder.install(Broken).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .starts_with("Could not install module broken: "));
+    }
+}
@@ -0,0 +1,311 @@
+use std::any::{type_name, TypeId};
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use super::ResolverKind;
+use crate::{Error, Result};
+
+/// Gets notified whenever the container resolves something.
+///
+/// Observers are added with
+/// [add_observer()](struct.ContainerBuilder.html#method.add_observer)
+/// and can be used for logging, metrics or auditing. Closures taking a
+/// `&ResolutionEvent` are observers too.
+///
+/// # Examples
+///
+/// ```
+/// # use std::cell::RefCell;
+/// # use std::rc::Rc;
+/// # use kamikaze_di::{ContainerBuilder, Resolver, ResolutionEvent};
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// let resolved = Rc::new(RefCell::new(vec![]));
+/// let log = resolved.clone();
+///
+/// let mut builder = ContainerBuilder::new();
+/// builder.register::<u32>(42)?;
+/// builder.add_observer(move |event: &ResolutionEvent| {
+///     if let ResolutionEvent::Finished { type_name, .. } = event {
+///         log.borrow_mut().push(type_name.to_string());
+///     }
+/// });
+///
+/// let container = builder.build();
+/// container.resolve::<u32>()?;
+///
+/// assert_eq!(vec!["u32".to_string()], *resolved.borrow());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub trait ResolutionObserver {
+    /// Receives a resolution event.
+    fn notify(&self, event: &ResolutionEvent);
+}
+
+impl<F> ResolutionObserver for F
+where
+    F: Fn(&ResolutionEvent),
+{
+    fn notify(&self, event: &ResolutionEvent) {
+        self(event)
+    }
+}
+
+/// Something that happened while resolving a type.
+#[derive(Clone, Copy, Debug)]
+pub enum ResolutionEvent<'a> {
+    /// The container started resolving a type.
+    Started {
+        /// Id of the type being resolved.
+        type_id: TypeId,
+        /// Name of the type being resolved.
+        type_name: &'static str,
+        /// How the type is resolved, None if it's not known to the container.
+        kind: Option<ResolverKind>,
+    },
+    /// The container finished resolving a type, successfully or not.
+    Finished {
+        /// Id of the type being resolved.
+        type_id: TypeId,
+        /// Name of the type being resolved.
+        type_name: &'static str,
+        /// How the type was resolved, None if it's not known to the container.
+        kind: Option<ResolverKind>,
+        /// Time spent resolving, including the time spent on dependencies.
+        duration: Duration,
+        /// Whether an already built item was used.
+        cache: CacheStatus,
+        /// The error, if resolving failed.
+        error: Option<&'a Error>,
+    },
+}
+
+/// Tells if a resolution used an already existing item.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum CacheStatus {
+    /// The item already existed inside the container.
+    Hit,
+    /// The item had to be created.
+    Miss,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Observers(Vec<Rc<dyn ResolutionObserver>>);
+
+impl Observers {
+    pub fn add(&mut self, observer: Rc<dyn ResolutionObserver>) {
+        self.0.push(observer);
+    }
+
+    pub fn append(&mut self, mut other: Observers) {
+        self.0.append(&mut other.0);
+    }
+
+    /// Runs resolve() for T and tells all observers about it.
+    pub fn observe<T, R, F>(
+        &self,
+        kind: Option<ResolverKind>,
+        cache: CacheStatus,
+        resolve: F,
+    ) -> Result<R>
+    where
+        T: 'static,
+        F: FnOnce() -> Result<R>,
+    {
+        if self.0.is_empty() {
+            return resolve();
+        }
+
+        let type_id = TypeId::of::<T>();
+        let type_name = type_name::<T>();
+
+        self.notify(&ResolutionEvent::Started {
+            type_id,
+            type_name,
+            kind,
+        });
+
+        let start = Instant::now();
+        let result = resolve();
+
+        self.notify(&ResolutionEvent::Finished {
+            type_id,
+            type_name,
+            kind,
+            duration: start.elapsed(),
+            cache,
+            error: result.as_ref().err(),
+        });
+
+        result
+    }
+
+    fn notify(&self, event: &ResolutionEvent) {
+        for observer in &self.0 {
+            observer.notify(event);
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::{CacheStatus, ResolutionEvent};
+    use crate::Container;
+    use crate::{ContainerBuilder, Inject, Injector, PerThread, Resolver, ResolverKind, Result};
+
+    type Recorded = Rc<RefCell<Vec<(String, Option<ResolverKind>, Option<CacheStatus>, bool)>>>;
+
+    fn recording_builder() -> (ContainerBuilder, Recorded) {
+        let recorded: Recorded = Default::default();
+        let log = recorded.clone();
+
+        let mut builder = ContainerBuilder::new();
+        builder.add_observer(move |event: &ResolutionEvent| {
+            let entry = match *event {
+                ResolutionEvent::Started {
+                    type_name, kind, ..
+                } => (type_name.to_string(), kind, None, false),
+                ResolutionEvent::Finished {
+                    type_name,
+                    kind,
+                    cache,
+                    error,
+                    ..
+                } => (type_name.to_string(), kind, Some(cache), error.is_some()),
+            };
+
+            log.borrow_mut().push(entry);
+        });
+
+        (builder, recorded)
+    }
+
+    #[test]
+    fn reports_cache_hits_and_misses() {
+        let (mut builder, recorded) = recording_builder();
+        builder.register::<i16>(42).unwrap();
+        builder.register_builder::<i32, _>(|_| 42).unwrap();
+
+        let container = builder.build();
+        container.resolve::<i16>().unwrap();
+        container.resolve::<i32>().unwrap();
+        container.resolve::<i32>().unwrap();
+
+        let finished: Vec<_> = recorded
+            .borrow()
+            .iter()
+            .filter_map(|(name, kind, cache, _)| cache.map(|c| (name.clone(), *kind, c)))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (
+                    "i16".to_string(),
+                    Some(ResolverKind::Shared),
+                    CacheStatus::Hit
+                ),
+                (
+                    "i32".to_string(),
+                    Some(ResolverKind::Builder),
+                    CacheStatus::Miss
+                ),
+                (
+                    "i32".to_string(),
+                    Some(ResolverKind::Builder),
+                    CacheStatus::Hit
+                ),
+            ],
+            finished
+        );
+    }
+
+    #[test]
+    fn reports_ttl_and_per_thread_hits() {
+        let (mut builder, recorded) = recording_builder();
+        builder
+            .register_builder_ttl::<i32, _>(Duration::from_secs(3600), |_| 42)
+            .unwrap();
+        builder.register_per_thread::<u8, _>(|| 42).unwrap();
+
+        let container = builder.build();
+        container.resolve::<i32>().unwrap();
+        container.resolve::<i32>().unwrap();
+        container.resolve::<PerThread<u8>>().unwrap();
+
+        let finished: Vec<_> = recorded
+            .borrow()
+            .iter()
+            .filter_map(|(_, kind, cache, _)| cache.map(|c| (*kind, c)))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (Some(ResolverKind::Ttl), CacheStatus::Miss),
+                (Some(ResolverKind::Ttl), CacheStatus::Hit),
+                (Some(ResolverKind::PerThread), CacheStatus::Hit),
+            ],
+            finished
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let (builder, recorded) = recording_builder();
+        let container = builder.build();
+
+        assert!(container.resolve::<i32>().is_err());
+
+        assert_eq!(
+            vec![
+                ("i32".to_string(), None, None, false),
+                ("i32".to_string(), None, Some(CacheStatus::Miss), true),
+            ],
+            *recorded.borrow()
+        );
+    }
+
+    #[test]
+    fn reports_auto_injected_types_once() {
+        #[derive(Clone)]
+        struct X;
+        impl Inject for X {
+            fn resolve(_: &Container) -> Result<X> {
+                Ok(X)
+            }
+        }
+
+        let (builder, recorded) = recording_builder();
+        let container = builder.build();
+
+        let _x: X = container.inject().unwrap();
+        let _x: X = container.inject().unwrap();
+
+        let kinds: Vec<_> = recorded
+            .borrow()
+            .iter()
+            .filter_map(|(_, kind, cache, _)| cache.map(|c| (*kind, c)))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (Some(ResolverKind::Injected), CacheStatus::Miss),
+                (Some(ResolverKind::Injected), CacheStatus::Hit),
+            ],
+            kinds
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use super::{Container, Entry, ResolverKind};
+use crate::Result;
+
+/// Items of per graph factories, kept until the outermost resolution
+/// finishes. Keyed by type and binding, 0 for the registration resolve()
+/// uses, see resolve_all().
+#[derive(Debug, Default)]
+pub(crate) struct GraphItems(RefCell<HashMap<(TypeId, usize), Box<dyn Any>>>);
+
+impl GraphItems {
+    pub fn has(&self, key: (TypeId, usize)) -> bool {
+        self.0.borrow().contains_key(&key)
+    }
+
+    /// Drops all items.
+    pub fn clear(&self) {
+        // bind the items, so they get dropped after the borrow ends
+        let _items = self.0.replace(HashMap::new());
+    }
+}
+
+impl Entry {
+    #[track_caller]
+    pub(super) fn per_graph<T, F>(factory: F) -> Entry
+    where
+        F: (FnMut(&Container) -> T) + Clone + 'static,
+        T: 'static,
+    {
+        Entry {
+            kind: ResolverKind::PerGraph,
+            ..Entry::factory::<T, F>(factory)
+        }
+    }
+}
+
+impl Container {
+    /// Calls the per graph factory of T, if it was not called during the
+    /// current resolution.
+    pub(super) fn call_per_graph<T: Clone + 'static>(&self, type_id: TypeId) -> Result<T> {
+        self.per_graph_item((type_id, 0), || self.call_factory::<T>(type_id))
+    }
+
+    /// The item stored under key during the current resolution, built
+    /// with build() if there is none.
+    pub(super) fn per_graph_item<T, F>(&self, key: (TypeId, usize), build: F) -> Result<T>
+    where
+        T: Clone + 'static,
+        F: FnOnce() -> Result<T>,
+    {
+        if let Some(item) = self.graph_items.0.borrow().get(&key) {
+            return Ok(item
+                .downcast_ref::<T>()
+                .expect("could not downcast per graph item")
+                .clone());
+        }
+
+        let item = build()?;
+
+        // bind the replaced item, so it gets dropped after the borrow ends
+        let _replaced = self
+            .graph_items
+            .0
+            .borrow_mut()
+            .insert(key, Box::new(item.clone()));
+
+        Ok(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::{Container, ContainerBuilder, Inject, Injector, Resolver, ResolverKind, Result};
+
+    struct UnitOfWork {
+        id: u32,
+    }
+
+    #[derive(Clone)]
+    struct Users {
+        work: Rc<UnitOfWork>,
+    }
+
+    #[derive(Clone)]
+    struct Orders {
+        work: Rc<UnitOfWork>,
+    }
+
+    #[derive(Clone)]
+    struct Handler {
+        users: Users,
+        orders: Orders,
+    }
+
+    impl Inject for Users {
+        fn resolve(container: &Container) -> Result<Self> {
+            Ok(Users {
+                work: container.resolve()?,
+            })
+        }
+    }
+
+    impl Inject for Orders {
+        fn resolve(container: &Container) -> Result<Self> {
+            Ok(Orders {
+                work: container.resolve()?,
+            })
+        }
+    }
+
+    impl Inject for Handler {
+        fn resolve(container: &Container) -> Result<Self> {
+            Ok(Handler {
+                users: container.resolve()?,
+                orders: container.resolve()?,
+            })
+        }
+    }
+
+    fn container() -> Container {
+        let ids = Cell::new(0);
+
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_per_graph::<Rc<UnitOfWork>, _>(move |_| {
+                ids.set(ids.get() + 1);
+                Rc::new(UnitOfWork { id: ids.get() })
+            })
+            .unwrap();
+        builder.register_automatic_factory::<Users>().unwrap();
+        builder.register_automatic_factory::<Orders>().unwrap();
+        builder.register_automatic_factory::<Handler>().unwrap();
+
+        builder.build()
+    }
+
+    #[test]
+    fn shares_items_within_one_resolution() {
+        let container = container();
+
+        let first: Handler = container.inject().unwrap();
+        let second: Handler = container.inject().unwrap();
+
+        assert!(Rc::ptr_eq(&first.users.work, &first.orders.work));
+        assert_eq!(1, first.users.work.id);
+        assert_eq!(2, second.users.work.id);
+    }
+
+    #[test]
+    fn top_level_resolutions_get_new_items() {
+        let container = container();
+
+        let first: Rc<UnitOfWork> = container.resolve().unwrap();
+        let second: Rc<UnitOfWork> = container.resolve().unwrap();
+
+        assert!(!Rc::ptr_eq(&first, &second));
+        assert_eq!(ResolverKind::PerGraph, container.registrations()[0].kind());
+    }
+}
@@ -0,0 +1,325 @@
+use std::any::{type_name, Any};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+
+use super::{Entry, Resolver, ResolverKind};
+
+/// Creates the item of every thread.
+type ThreadFactory<T> = dyn Fn() -> T + Send + Sync;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Items of this thread, by PerThread id.
+    static ITEMS: RefCell<HashMap<usize, Slot>> = RefCell::new(HashMap::new());
+}
+
+/// The item of one PerThread on one thread.
+struct Slot {
+    /// Dead once every clone of the PerThread is dropped.
+    owner: Weak<()>,
+    /// None while being built.
+    item: Option<Rc<dyn Any>>,
+}
+
+/// One item per OS thread.
+///
+/// Registered with
+/// [register_per_thread()](struct.ContainerBuilder.html#method.register_per_thread),
+/// and resolved as PerThread<T>. It's a handle that can be cloned and
+/// sent to other threads. [get()](struct.PerThread.html#method.get)
+/// builds the item of the current thread the first time it's called
+/// on that thread, items don't have to be Send or Sync.
+///
+/// The container itself stays on one thread, so the factory does not
+/// get it, nor any other way to resolve dependencies. Resolve them
+/// first and move them into the factory, they have to be Send and Sync,
+/// see the second example.
+///
+/// The factory runs outside of the container: observers don't see it,
+/// and the container's cycle detection does not track it. Instead, the
+/// item is marked as being built on the current thread while the factory
+/// runs. Calling get() on the same PerThread on that thread before the
+/// factory returns, directly or through the factory of another
+/// PerThread, panics with "Circular dependency detected when building T
+/// for this thread". If the factory panics, the mark is removed and the
+/// next get() on that thread calls the factory again. Resolving
+/// PerThread<T> from the container only clones the registered handle,
+/// it never calls the factory, so it can't re-enter it: a factory that
+/// was given a handle to its own PerThread panics as above once it
+/// calls get().
+///
+/// Each thread keeps its items in thread local storage. They are dropped
+/// when the thread exits, or once every clone of the PerThread is
+/// dropped, the next time that thread builds an item.
+///
+/// # Examples
+///
+/// ```
+/// # use std::cell::Cell;
+/// # use std::thread;
+/// # use kamikaze_di::{ContainerBuilder, PerThread, Resolver};
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// let mut builder = ContainerBuilder::new();
+/// builder.register_per_thread::<Cell<u32>, _>(|| Cell::new(0))?;
+///
+/// let container = builder.build();
+///
+/// let counter: PerThread<Cell<u32>> = container.resolve()?;
+/// counter.get().set(42);
+///
+/// let other_thread = counter.clone();
+/// thread::spawn(move || assert_eq!(0, other_thread.get().get()))
+///     .join()
+///     .unwrap();
+///
+/// assert_eq!(42, counter.get().get());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+///
+/// With dependencies:
+///
+/// ```
+/// # use std::cell::RefCell;
+/// # use std::sync::Arc;
+/// # use kamikaze_di::{ContainerBuilder, PerThread, Resolver};
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// let mut builder = ContainerBuilder::new();
+/// builder.register::<Arc<String>>(Arc::new("> ".to_string()))?;
+///
+/// let container = builder.build();
+///
+/// let prefix: Arc<String> = container.resolve()?;
+/// container.register_per_thread::<RefCell<String>, _>(move || {
+///     RefCell::new(prefix.to_string())
+/// })?;
+///
+/// let buffer: PerThread<RefCell<String>> = container.resolve()?;
+/// assert_eq!("> ", *buffer.get().borrow());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct PerThread<T> {
+    id: usize,
+    factory: Arc<ThreadFactory<T>>,
+    /// Shared by all clones, items of dropped handles are swept by build().
+    owner: Arc<()>,
+}
+
+impl<T: 'static> PerThread<T> {
+    pub(super) fn new<F>(factory: F) -> PerThread<T>
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        PerThread {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            factory: Arc::new(factory),
+            owner: Arc::new(()),
+        }
+    }
+
+    /// Returns the item of the current thread, building it if needed.
+    pub fn get(&self) -> Rc<T> {
+        let built = ITEMS.with(|items| match items.borrow().get(&self.id) {
+            Some(Slot {
+                item: Some(item), ..
+            }) => Some(item.clone()),
+            Some(Slot { item: None, .. }) => panic!(
+                "Circular dependency detected when building {} for this thread",
+                type_name::<T>()
+            ),
+            None => None,
+        });
+
+        let item = match built {
+            Some(item) => item,
+            None => self.build(),
+        };
+
+        item.downcast().expect("could not downcast per thread item")
+    }
+
+    fn build(&self) -> Rc<dyn Any> {
+        sweep();
+
+        self.fill(None);
+        let _guard = BuildingGuard(self.id);
+
+        // the factory runs without the items borrowed, it can get other items
+        let item: Rc<dyn Any> = Rc::new((self.factory)());
+
+        self.fill(Some(item.clone()));
+
+        item
+    }
+
+    fn fill(&self, item: Option<Rc<dyn Any>>) {
+        let slot = Slot {
+            owner: Arc::downgrade(&self.owner),
+            item,
+        };
+
+        // bind the replaced slot, so it gets dropped after the borrow ends
+        let _replaced = ITEMS.with(|items| items.borrow_mut().insert(self.id, slot));
+    }
+}
+
+/// Drops the items of this thread whose PerThread handles are all gone.
+fn sweep() {
+    // bind the dropped slots, their items can use other PerThreads when dropped
+    let _dropped: Vec<Slot> = ITEMS.with(|items| {
+        let mut items = items.borrow_mut();
+        let dead: Vec<usize> = items
+            .iter()
+            .filter(|(_, slot)| slot.owner.strong_count() == 0)
+            .map(|(id, _)| *id)
+            .collect();
+
+        dead.iter().filter_map(|id| items.remove(id)).collect()
+    });
+}
+
+/// Forgets an item that failed to build, so the next get() tries again.
+struct BuildingGuard(usize);
+
+impl Drop for BuildingGuard {
+    fn drop(&mut self) {
+        let _ = ITEMS.try_with(|items| {
+            let mut items = items.borrow_mut();
+
+            if let Some(Slot { item: None, .. }) = items.get(&self.0) {
+                items.remove(&self.0);
+            }
+        });
+    }
+}
+
+impl<T> Clone for PerThread<T> {
+    fn clone(&self) -> Self {
+        PerThread {
+            id: self.id,
+            factory: self.factory.clone(),
+            owner: self.owner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for PerThread<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PerThread<{}>({})", type_name::<T>(), self.id)
+    }
+}
+
+impl Entry {
+    #[track_caller]
+    pub(super) fn per_thread<T: 'static>(resolver: Resolver) -> Entry {
+        Entry {
+            kind: ResolverKind::PerThread,
+            ..Entry::registered::<PerThread<T>>(resolver)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{PerThread, ITEMS};
+    use crate::{ContainerBuilder, Resolver, ResolverKind};
+
+    #[test]
+    fn builds_one_item_per_thread() {
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_per_thread::<Cell<u32>, _>(|| Cell::new(1))
+            .unwrap();
+
+        let container = builder.build();
+        let counter: PerThread<Cell<u32>> = container.resolve().unwrap();
+
+        assert!(Rc::ptr_eq(&counter.get(), &counter.get()));
+        counter.get().set(2);
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let counter = counter.clone();
+
+                thread::spawn(move || {
+                    counter.get().set(counter.get().get() + 10);
+                    counter.get().get()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(11, handle.join().unwrap());
+        }
+
+        assert_eq!(2, counter.get().get());
+        assert_eq!(ResolverKind::PerThread, container.registrations()[0].kind());
+    }
+
+    #[test]
+    #[should_panic(expected = "Circular dependency")]
+    fn panics_on_circular_dependencies() {
+        let id = PerThread::<u32>::new(|| 1).id;
+
+        // a handle whose factory gets its own item
+        let looping = PerThread::<u32> {
+            id,
+            factory: Arc::new(move || {
+                let same = PerThread::<u32> {
+                    id,
+                    factory: Arc::new(|| 1),
+                    owner: Arc::new(()),
+                };
+
+                *same.get()
+            }),
+            owner: Arc::new(()),
+        };
+
+        looping.get();
+    }
+
+    #[test]
+    fn items_of_dropped_handles_are_dropped() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Tracked;
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let first = PerThread::<Tracked>::new(|| Tracked);
+        let clone = first.clone();
+        first.get();
+        drop(first);
+
+        PerThread::<u8>::new(|| 1).get();
+        assert_eq!(0, DROPPED.load(Ordering::Relaxed));
+
+        drop(clone);
+
+        PerThread::<u8>::new(|| 2).get();
+        assert_eq!(1, DROPPED.load(Ordering::Relaxed));
+        assert_eq!(1, ITEMS.with(|items| items.borrow().len()));
+    }
+}
@@ -0,0 +1,471 @@
+use std::any::{type_name, TypeId};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use super::resolver::Resolver as _;
+use super::{Container, Entry, Resolver};
+use crate::Result;
+
+/// Resets pooled objects before they are reused.
+type Reset<T> = dyn Fn(&mut T) + Send + Sync;
+
+/// A fixed number of reusable objects.
+///
+/// Registered with
+/// [register_pool()](struct.ContainerBuilder.html#method.register_pool),
+/// all objects are created the first time the pool is resolved. Objects
+/// are taken out with [checkout()](struct.Pool.html#method.checkout) or
+/// [try_checkout()](struct.Pool.html#method.try_checkout), and go back
+/// into the pool when the [Pooled](struct.Pooled.html) guard is dropped.
+///
+/// Pools are cloned by reference, and can be sent to other threads.
+///
+/// # Examples
+///
+/// ```
+/// # use kamikaze_di::{ContainerBuilder, Pool, Resolver};
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// let mut builder = ContainerBuilder::new();
+/// builder.register_pool::<Vec<u8>, _>(2, |_| Vec::with_capacity(1024))?;
+///
+/// let container = builder.build();
+///
+/// let pool: Pool<Vec<u8>> = container.resolve()?;
+/// let mut buffer = pool.checkout();
+/// buffer.push(42);
+///
+/// assert_eq!(1, pool.stats().checked_out());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Pool<T> {
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T> {
+    state: Mutex<State<T>>,
+    returned: Condvar,
+    reset: Option<Box<Reset<T>>>,
+    size: usize,
+}
+
+struct State<T> {
+    idle: Vec<T>,
+    checkouts: u64,
+    waits: u64,
+}
+
+/// Usage of a [Pool](struct.Pool.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PoolStats {
+    size: usize,
+    idle: usize,
+    checkouts: u64,
+    waits: u64,
+}
+
+impl PoolStats {
+    /// How many objects the pool has.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// How many objects are in the pool.
+    pub fn idle(&self) -> usize {
+        self.idle
+    }
+
+    /// How many objects are checked out.
+    pub fn checked_out(&self) -> usize {
+        self.size - self.idle
+    }
+
+    /// How many times an object was checked out.
+    pub fn checkouts(&self) -> u64 {
+        self.checkouts
+    }
+
+    /// How many checkouts found the pool empty, and had to wait or fail.
+    pub fn waits(&self) -> u64 {
+        self.waits
+    }
+}
+
+impl<T: Send + 'static> Pool<T> {
+    fn new(items: Vec<T>, reset: Option<Box<Reset<T>>>) -> Pool<T> {
+        Pool {
+            inner: Arc::new(Inner {
+                size: items.len(),
+                state: Mutex::new(State {
+                    idle: items,
+                    checkouts: 0,
+                    waits: 0,
+                }),
+                returned: Condvar::new(),
+                reset,
+            }),
+        }
+    }
+
+    /// Takes an object out of the pool, waiting for one to be returned
+    /// if there are none left.
+    ///
+    /// Waiting only ends when another thread drops its
+    /// [Pooled](struct.Pooled.html) guard. On a single thread, use
+    /// [try_checkout()](struct.Pool.html#method.try_checkout) instead.
+    pub fn checkout(&self) -> Pooled<T> {
+        let mut state = self.inner.lock();
+
+        if state.idle.is_empty() {
+            state.waits += 1;
+        }
+
+        loop {
+            if let Some(item) = state.idle.pop() {
+                state.checkouts += 1;
+
+                return self.pooled(item);
+            }
+
+            state = self
+                .inner
+                .returned
+                .wait(state)
+                .expect("could not lock pool");
+        }
+    }
+
+    /// Takes an object out of the pool, None if there are none left.
+    pub fn try_checkout(&self) -> Option<Pooled<T>> {
+        let mut state = self.inner.lock();
+
+        match state.idle.pop() {
+            Some(item) => {
+                state.checkouts += 1;
+
+                Some(self.pooled(item))
+            }
+            None => {
+                state.waits += 1;
+
+                None
+            }
+        }
+    }
+
+    /// Returns the usage of the pool.
+    pub fn stats(&self) -> PoolStats {
+        let state = self.inner.lock();
+
+        PoolStats {
+            size: self.inner.size,
+            idle: state.idle.len(),
+            checkouts: state.checkouts,
+            waits: state.waits,
+        }
+    }
+
+    fn pooled(&self, item: T) -> Pooled<T> {
+        Pooled {
+            item: Some(item),
+            pool: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Inner<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().expect("could not lock pool")
+    }
+}
+
+impl<T> Clone for Pool<T> {
+    fn clone(&self) -> Self {
+        Pool {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Pool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Pool<{}>({})", type_name::<T>(), self.inner.size)
+    }
+}
+
+/// An object checked out of a [Pool](struct.Pool.html).
+///
+/// Dereferences to the object. Dropping it runs the reset hook, if the
+/// pool has one, and returns the object to the pool, even if the hook
+/// panics.
+pub struct Pooled<T> {
+    // only None while being dropped
+    item: Option<T>,
+    pool: Arc<Inner<T>>,
+}
+
+impl<T> Deref for Pooled<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().expect("pooled object was returned")
+    }
+}
+
+impl<T> DerefMut for Pooled<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().expect("pooled object was returned")
+    }
+}
+
+impl<T> Drop for Pooled<T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            let mut returning = Returning {
+                item: Some(item),
+                pool: &self.pool,
+            };
+
+            if let Some(reset) = &self.pool.reset {
+                reset(returning.item.as_mut().expect("pooled object was returned"));
+            }
+        }
+    }
+}
+
+/// Puts an object back into its pool, even when the reset hook panics.
+struct Returning<'a, T> {
+    item: Option<T>,
+    pool: &'a Inner<T>,
+}
+
+impl<'a, T> Drop for Returning<'a, T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.pool.lock().idle.push(item);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Pooled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Pooled").field(&self.item).finish()
+    }
+}
+
+impl Entry {
+    /// Entry for Pool<T>, with a builder creating all its objects with factory.
+    ///
+    /// Empty pools are errors, checkout() would wait forever.
+    #[track_caller]
+    pub(super) fn pool<T, F>(size: usize, factory: F, reset: Option<Box<Reset<T>>>) -> Result<Entry>
+    where
+        F: (FnMut(&Container) -> T) + 'static,
+        T: Send + 'static,
+    {
+        if size == 0 {
+            return Err(format!(
+                "{} can't be empty, checkout() would wait forever",
+                type_name::<Pool<T>>()
+            )
+            .into());
+        }
+
+        let resolver = Resolver::builder(pool_builder(size, factory, reset));
+
+        Ok(Entry {
+            pool_size: Some(size),
+            ..Entry::registered::<Pool<T>>(resolver)
+        })
+    }
+}
+
+/// Builder for a Pool<T>, creating all its objects with factory.
+fn pool_builder<T, F>(
+    size: usize,
+    mut factory: F,
+    reset: Option<Box<Reset<T>>>,
+) -> impl FnOnce(&Container) -> Pool<T>
+where
+    F: (FnMut(&Container) -> T) + 'static,
+    T: Send + 'static,
+{
+    move |container| Pool::new((0..size).map(|_| factory(container)).collect(), reset)
+}
+
+impl Container {
+    /// Takes an object out of the pool registered for T, waiting if
+    /// there are none left.
+    ///
+    /// See [Pool::checkout()](struct.Pool.html#method.checkout).
+    pub fn checkout<T: Send + 'static>(&self) -> Result<Pooled<T>> {
+        Ok(self.resolve::<Pool<T>>()?.checkout())
+    }
+
+    /// Takes an object out of the pool registered for T, None if there
+    /// are none left.
+    ///
+    /// See [Pool::try_checkout()](struct.Pool.html#method.try_checkout).
+    pub fn try_checkout<T: Send + 'static>(&self) -> Result<Option<Pooled<T>>> {
+        Ok(self.resolve::<Pool<T>>()?.try_checkout())
+    }
+
+    /// Returns the usage of the pool registered for T.
+    ///
+    /// Pools that were not built yet are not built by this, their stats
+    /// come from the registration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::ContainerBuilder;
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_pool::<String, _>(1, |_| String::new())?;
+    ///
+    /// let container = builder.build();
+    ///
+    /// let name = container.try_checkout::<String>()?;
+    /// assert!(name.is_some());
+    /// assert!(container.try_checkout::<String>()?.is_none());
+    ///
+    /// let stats = container.pool_stats::<String>()?;
+    /// assert_eq!(1, stats.checked_out());
+    /// assert_eq!(1, stats.waits());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pool_stats<T: Send + 'static>(&self) -> Result<PoolStats> {
+        let pending = self
+            .entries
+            .borrow()
+            .get(&TypeId::of::<Pool<T>>())
+            .filter(|entry| !entry.holds_item())
+            .and_then(|entry| entry.pool_size);
+
+        match pending {
+            Some(size) => Ok(PoolStats {
+                size,
+                idle: size,
+                checkouts: 0,
+                waits: 0,
+            }),
+            None => Ok(self.resolve::<Pool<T>>()?.stats()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+    use std::thread;
+
+    use super::Pool;
+    use crate::{Container, ContainerBuilder, Resolver};
+
+    #[test]
+    fn objects_are_reused_and_reset() {
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_pool_with_reset::<Vec<u8>, _, _>(2, |_| vec![], |buffer| buffer.clear())
+            .unwrap();
+
+        let container = builder.build();
+
+        {
+            let mut first = container.checkout::<Vec<u8>>().unwrap();
+            let second = container.try_checkout::<Vec<u8>>().unwrap();
+
+            first.push(1);
+
+            assert!(second.is_some());
+            assert!(container.try_checkout::<Vec<u8>>().unwrap().is_none());
+        }
+
+        let stats = container.pool_stats::<Vec<u8>>().unwrap();
+        assert_eq!(2, stats.idle());
+        assert_eq!(2, stats.checkouts());
+        assert_eq!(1, stats.waits());
+
+        let buffer = container.checkout::<Vec<u8>>().unwrap();
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn checkout_waits_for_other_threads() {
+        let mut builder = ContainerBuilder::new();
+        builder.register_pool::<u32, _>(1, |_| 42).unwrap();
+
+        let container = builder.build();
+        let pool: Pool<u32> = container.resolve().unwrap();
+
+        let taken = pool.checkout();
+        let other = thread::spawn(move || {
+            drop(taken);
+        });
+
+        assert_eq!(42, *pool.checkout());
+        other.join().unwrap();
+    }
+
+    #[test]
+    fn empty_pools_are_errors() {
+        let mut builder = ContainerBuilder::new();
+
+        assert!(builder.register_pool::<u32, _>(0, |_| 42).is_err());
+        assert!(Container::new().register_pool::<u32, _>(0, |_| 42).is_err());
+    }
+
+    #[test]
+    fn stats_do_not_build_the_pool() {
+        let created = Rc::new(Cell::new(0));
+        let count = created.clone();
+
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_pool::<u32, _>(3, move |_| {
+                count.set(count.get() + 1);
+                42
+            })
+            .unwrap();
+
+        let container = builder.build();
+
+        let stats = container.pool_stats::<u32>().unwrap();
+        assert_eq!(3, stats.size());
+        assert_eq!(3, stats.idle());
+        assert_eq!(0, stats.checkouts());
+        assert_eq!(Some(3), container.registrations()[0].pool_size());
+        assert_eq!(0, created.get());
+
+        let _checked_out = container.checkout::<u32>().unwrap();
+        assert_eq!(1, container.pool_stats::<u32>().unwrap().checked_out());
+        assert_eq!(3, created.get());
+    }
+
+    #[test]
+    fn objects_are_returned_when_reset_panics() {
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_pool_with_reset::<u32, _, _>(1, |_| 42, |_| panic!("could not reset"))
+            .unwrap();
+
+        let container = builder.build();
+        let pooled = container.checkout::<u32>().unwrap();
+
+        let dropped = panic::catch_unwind(AssertUnwindSafe(|| drop(pooled)));
+
+        assert!(dropped.is_err());
+        assert_eq!(1, container.pool_stats::<u32>().unwrap().idle());
+    }
+}
@@ -0,0 +1,174 @@
+use std::panic::Location;
+
+use super::Container;
+
+/// Registrations that were skipped, because their profile was not active
+/// or their condition did not hold.
+///
+/// Skipped registrations are never run, so the types they would have
+/// registered are not known. They are told apart by the place
+/// [when_profile()](struct.ContainerBuilder.html#method.when_profile) or
+/// [when()](struct.ContainerBuilder.html#method.when) was called from,
+/// and by the [Module](trait.Module.html) being installed at the time.
+///
+/// Returned by [ContainerBuilder::skipped()](struct.ContainerBuilder.html#method.skipped)
+/// and [Container::skipped()](struct.Container.html#method.skipped).
+///
+/// # Examples
+///
+/// ```
+/// # use kamikaze_di::ContainerBuilder;
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// let mut builder = ContainerBuilder::new().with_profiles(vec!["test"]);
+/// builder.when_profile("prod", |builder| builder.register::<u16>(3306))?;
+///
+/// let skipped = &builder.skipped()[0];
+///
+/// assert_eq!("profile prod is not active", skipped.reason());
+/// assert_eq!(file!(), skipped.registered_at().file());
+/// assert_eq!(None, skipped.module());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SkippedRegistration {
+    registered_at: &'static Location<'static>,
+    module: Option<&'static str>,
+    reason: String,
+}
+
+impl SkippedRegistration {
+    /// Where the registrations would have been made.
+    pub fn registered_at(&self) -> &'static Location<'static> {
+        self.registered_at
+    }
+
+    /// Name of the module that would have made the registrations, None
+    /// outside of modules.
+    pub fn module(&self) -> Option<&'static str> {
+        self.module
+    }
+
+    /// Why the registrations were skipped.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    pub(super) fn new(
+        reason: String,
+        registered_at: &'static Location<'static>,
+        module: Option<&'static str>,
+    ) -> Self {
+        SkippedRegistration {
+            registered_at,
+            module,
+            reason,
+        }
+    }
+}
+
+impl Container {
+    /// Lists the registrations skipped by the builder, in the order they
+    /// were skipped.
+    ///
+    /// See [SkippedRegistration](struct.SkippedRegistration.html).
+    pub fn skipped(&self) -> &[SkippedRegistration] {
+        &self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ContainerBuilder, Module, Resolver, Result};
+
+    fn builder(profiles: Vec<&str>) -> ContainerBuilder {
+        let mut builder = ContainerBuilder::new().with_profiles(profiles);
+        builder
+            .when_profile("test", |builder| builder.register::<&str>("in memory"))
+            .unwrap();
+        builder
+            .when_profile("prod", |builder| builder.register::<&str>("mysql"))
+            .unwrap();
+
+        builder
+    }
+
+    #[test]
+    fn skipped_registrations_are_not_run() {
+        let mut builder = ContainerBuilder::new().with_profiles(vec!["test"]);
+        builder
+            .when_profile("prod", |_| panic!("connecting to the database"))
+            .unwrap();
+        builder
+            .when("never", |_| false, |_| panic!("reading the environment"))
+            .unwrap();
+
+        assert_eq!(2, builder.skipped().len());
+    }
+
+    #[test]
+    fn registers_active_profiles_only() {
+        let test = builder(vec!["test"]).build();
+        let prod = builder(vec!["prod"]).build();
+
+        assert_eq!("in memory", test.resolve::<&str>().unwrap());
+        assert_eq!("mysql", prod.resolve::<&str>().unwrap());
+        assert!(builder(vec![]).build().resolve::<&str>().is_err());
+    }
+
+    #[test]
+    fn lists_skipped_registrations() {
+        let container = builder(vec!["test"]).build();
+
+        let skipped = container.skipped();
+
+        assert_eq!(1, skipped.len());
+        assert_eq!("profile prod is not active", skipped[0].reason());
+        assert_eq!(file!(), skipped[0].registered_at().file());
+    }
+
+    #[test]
+    fn lists_the_module_of_skipped_registrations() {
+        struct Database;
+
+        impl Module for Database {
+            fn configure(&self, builder: &mut ContainerBuilder) -> Result<()> {
+                builder.when_profile("prod", |builder| builder.register::<&str>("mysql"))
+            }
+
+            fn name(&self) -> &'static str {
+                "database"
+            }
+        }
+
+        let mut builder = ContainerBuilder::new();
+        builder.install(Database).unwrap();
+
+        assert_eq!(Some("database"), builder.skipped()[0].module());
+    }
+
+    #[test]
+    fn conditions_can_check_the_builder() {
+        let mut builder = ContainerBuilder::new();
+        builder.register::<u32>(42).unwrap();
+        builder
+            .when(
+                "no port registered",
+                |builder| !builder.has::<u32>(),
+                |builder| builder.register::<u32>(8080),
+            )
+            .unwrap();
+
+        let skipped = builder.skipped();
+        assert_eq!(
+            "condition no port registered does not hold",
+            skipped[0].reason()
+        );
+
+        let container = builder.build();
+        assert_eq!(42, container.resolve::<u32>().unwrap());
+    }
+}
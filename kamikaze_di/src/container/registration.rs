@@ -0,0 +1,198 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::panic::Location;
+
+use super::{Container, Entry, Resolver, ResolverKind};
+
+/// Information about something known to a container.
+///
+/// Returned by [Container::registrations()](struct.Container.html#method.registrations)
+/// and [ContainerBuilder::registrations()](struct.ContainerBuilder.html#method.registrations).
+#[derive(Clone, Copy, Debug)]
+pub struct Registration {
+    type_id: TypeId,
+    type_name: &'static str,
+    kind: ResolverKind,
+    built: bool,
+    eager: bool,
+    resolutions: usize,
+    registered_at: Option<&'static Location<'static>>,
+    module: Option<&'static str>,
+    pool_size: Option<usize>,
+}
+
+impl Registration {
+    /// Id of the registered type.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Name of the registered type.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// How the type was registered.
+    ///
+    /// Types resolved through [Inject](trait.Inject.html) or
+    /// [InjectAsRc](trait.InjectAsRc.html) are reported as
+    /// [ResolverKind::Injected](enum.ResolverKind.html#variant.Injected).
+    pub fn kind(&self) -> ResolverKind {
+        self.kind
+    }
+
+    /// True if the container holds an instance of the type.
+    ///
+    /// Builders are built after they are first resolved, factories are
+    /// never built. Keyed builders are built while they have items for
+    /// any key.
+    pub fn is_built(&self) -> bool {
+        self.built
+    }
+
+    /// True if the type is built by
+    /// [build_eager()](struct.ContainerBuilder.html#method.build_eager), see
+    /// [eager()](struct.ContainerBuilder.html#method.eager).
+    pub fn is_eager(&self) -> bool {
+        self.eager
+    }
+
+    /// How many times the type was resolved.
+    pub fn resolutions(&self) -> usize {
+        self.resolutions
+    }
+
+    /// Where the type was registered, None for types that were not
+    /// registered explicitly.
+    pub fn registered_at(&self) -> Option<&'static Location<'static>> {
+        self.registered_at
+    }
+
+    /// Name of the [Module](trait.Module.html) that registered the type,
+    /// None for types registered outside of modules.
+    pub fn module(&self) -> Option<&'static str> {
+        self.module
+    }
+
+    /// Number of objects in the pool, for types registered with
+    /// [register_pool()](struct.ContainerBuilder.html#method.register_pool).
+    pub fn pool_size(&self) -> Option<usize> {
+        self.pool_size
+    }
+
+    pub(super) fn collect(entries: &HashMap<TypeId, Entry>) -> Vec<Registration> {
+        let mut registrations: Vec<_> = entries
+            .iter()
+            .map(|(type_id, entry)| Registration::new(*type_id, entry))
+            .collect();
+
+        registrations.sort_by_key(|registration| registration.type_name);
+
+        registrations
+    }
+
+    fn new(type_id: TypeId, entry: &Entry) -> Registration {
+        let built = match &entry.resolver {
+            Resolver::Shared(_) => true,
+            Resolver::Keyed(keyed) => keyed.cached() > 0,
+            Resolver::Factory(_) | Resolver::Builder(_) => false,
+        };
+
+        Registration {
+            type_id,
+            type_name: entry.type_name,
+            kind: entry.kind,
+            built,
+            eager: entry.eager,
+            resolutions: entry.resolutions.get(),
+            registered_at: entry.registered_at,
+            module: entry.module,
+            pool_size: entry.pool_size,
+        }
+    }
+}
+
+impl Container {
+    /// Lists everything the container knows about, sorted by type name.
+    ///
+    /// See [Registration](struct.Registration.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, Resolver, ResolverKind};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_builder::<i32, _>(|_| 42)?;
+    ///
+    /// let container = builder.build();
+    /// assert!(!container.registrations()[0].is_built());
+    ///
+    /// container.resolve::<i32>()?;
+    /// container.resolve::<i32>()?;
+    ///
+    /// let registration = &container.registrations()[0];
+    /// assert_eq!(ResolverKind::Builder, registration.kind());
+    /// assert!(registration.is_built());
+    /// assert_eq!(2, registration.resolutions());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn registrations(&self) -> Vec<Registration> {
+        Registration::collect(&self.entries.borrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Container, ContainerBuilder, Inject, Injector, ResolverKind, Result};
+
+    #[test]
+    fn injected_types_are_listed() {
+        #[derive(Clone)]
+        struct X;
+        impl Inject for X {
+            fn resolve(_: &Container) -> Result<X> {
+                Ok(X)
+            }
+        }
+
+        let container = Container::new();
+        let _x: X = container.inject().unwrap();
+        let _x: X = container.inject().unwrap();
+
+        let registrations = container.registrations();
+
+        assert_eq!(1, registrations.len());
+        assert_eq!(ResolverKind::Injected, registrations[0].kind());
+        assert!(registrations[0].is_built());
+        assert_eq!(2, registrations[0].resolutions());
+        assert!(registrations[0].registered_at().is_none());
+    }
+
+    #[test]
+    fn registration_site_is_recorded() {
+        let mut builder = ContainerBuilder::new();
+        let line = line!() + 1;
+        builder.register::<i32>(42).unwrap();
+
+        let registration = &builder.registrations()[0];
+        let location = registration.registered_at().unwrap();
+
+        assert_eq!(file!(), location.file());
+        assert_eq!(line, location.line());
+
+        let container = Container::new();
+        let line = line!() + 1;
+        container.register_builder::<i32, _>(|_| 42).unwrap();
+
+        let registration = &container.registrations()[0];
+        let location = registration.registered_at().unwrap();
+
+        assert_eq!(file!(), location.file());
+        assert_eq!(line, location.line());
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use super::Container;
+use crate::Result;
+
+/// A shared dependency, borrowed from the container.
+///
+/// Returned by [Container::resolve_ref()](struct.Container.html#method.resolve_ref).
+/// The container can still be used while you hold on to it. If the
+/// dependency gets replaced or removed in the meantime, this keeps
+/// pointing to the old value.
+pub struct SharedRef<'a, T> {
+    item: Rc<T>,
+    container: PhantomData<&'a Container>,
+}
+
+impl<'a, T> Deref for SharedRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for SharedRef<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.item.fmt(f)
+    }
+}
+
+impl Container {
+    /// Borrows a shared dependency, without cloning it.
+    ///
+    /// Works for dependencies registered as items or builders, which
+    /// don't need to implement Clone. Builders are called if needed.
+    /// Factories can't be borrowed, since they don't keep what they create.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// // does not implement Clone
+    /// struct LookupTable { entries: Vec<u32> }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register(LookupTable { entries: vec![1, 2, 3] })?;
+    ///
+    /// let container = builder.build();
+    /// let table = container.resolve_ref::<LookupTable>()?;
+    ///
+    /// assert_eq!(3, table.entries.len());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_ref<T: 'static>(&self) -> Result<SharedRef<'_, T>> {
+        Ok(SharedRef {
+            item: self.get_ref()?,
+            container: PhantomData,
+        })
+    }
+
+    /// Calls f() with a borrowed shared dependency.
+    ///
+    /// See [resolve_ref()](struct.Container.html#method.resolve_ref).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// struct Config { db: String }
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register(Config { db: "localhost".to_string() })?;
+    ///
+    /// let container = builder.build();
+    /// let db_length = container.with(|config: &Config| config.db.len())?;
+    ///
+    /// assert_eq!(9, db_length);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with<T, R, F>(&self, f: F) -> Result<R>
+    where
+        T: 'static,
+        F: FnOnce(&T) -> R,
+    {
+        let item = self.resolve_ref::<T>()?;
+
+        Ok(f(&item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Container, ContainerBuilder};
+
+    #[derive(Debug)]
+    struct Table(Vec<u32>);
+
+    #[test]
+    fn builders_get_called() {
+        let mut builder = ContainerBuilder::new();
+        builder.register_builder(|_| Table(vec![1, 2, 3])).unwrap();
+
+        let container = builder.build();
+        let table = container.resolve_ref::<Table>().unwrap();
+
+        assert_eq!(vec![1, 2, 3], table.0);
+        assert!(container.registrations()[0].is_built());
+    }
+
+    #[test]
+    fn factories_cant_be_borrowed() {
+        let container = Container::new();
+        container.register_factory(|_| Table(vec![])).unwrap();
+
+        let error: String = container.resolve_ref::<Table>().unwrap_err().into();
+
+        assert!(error.contains("factory"));
+    }
+
+    #[test]
+    fn borrowing_does_not_block_the_container() {
+        let container = Container::new();
+        container.register(Table(vec![1])).unwrap();
+
+        let old = container.resolve_ref::<Table>().unwrap();
+        container.replace(Table(vec![2])).unwrap();
+        let new = container.resolve_ref::<Table>().unwrap();
+
+        assert_eq!(vec![1], old.0);
+        assert_eq!(vec![2], new.0);
+    }
+}
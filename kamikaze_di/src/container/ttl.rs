@@ -0,0 +1,358 @@
+use std::any::{type_name, TypeId};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use super::{Container, Entry, Forker, Resolver, ResolverKind};
+use crate::{Error, Result};
+
+/// Where builders with a time to live get the time from.
+///
+/// Builders registered with
+/// [register_builder_ttl()](struct.ContainerBuilder.html#method.register_builder_ttl)
+/// use the `Rc<dyn Clock>` registered in the container, or the
+/// [SystemClock](struct.SystemClock.html) if there is none. Register a
+/// [ManualClock](struct.ManualClock.html) to test expiry without sleeping.
+pub trait Clock {
+    /// The current time.
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// # Examples
+///
+/// ```
+/// # use std::rc::Rc;
+/// # use std::time::Duration;
+/// # use kamikaze_di::{Clock, ContainerBuilder, ManualClock, Resolver};
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// let clock = Rc::new(ManualClock::new());
+///
+/// let mut builder = ContainerBuilder::new();
+/// builder.register::<Rc<dyn Clock>>(clock.clone())?;
+/// builder.register_builder_ttl::<String, _>(Duration::from_secs(60), |_| "token".to_string())?;
+///
+/// let container = builder.build();
+/// container.resolve::<String>()?;
+///
+/// clock.advance(Duration::from_secs(60));
+/// container.resolve::<String>()?; // built again
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Cell<Instant>,
+}
+
+impl ManualClock {
+    /// Creates a clock set to the current time.
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Cell::new(Instant::now()),
+        }
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+/// Item cache of a builder with a time to live, see Entry::expiry.
+pub(super) trait Expiry: fmt::Debug {
+    /// Forgets the item, so the builder runs on the next resolve.
+    fn invalidate(&self);
+
+    /// True if the item is built and not expired.
+    fn is_cached(&self, container: &Container) -> bool;
+}
+
+struct TtlCache<T> {
+    ttl: Duration,
+    /// The item, with the time it was built at.
+    built: RefCell<Option<(T, Instant)>>,
+}
+
+impl<T> TtlCache<T> {
+    /// Clones the item, if it's not expired.
+    fn get(&self, now: Instant) -> Option<T>
+    where
+        T: Clone,
+    {
+        match &*self.built.borrow() {
+            Some((item, built_at)) if now < *built_at + self.ttl => Some(item.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Expiry for TtlCache<T> {
+    fn invalidate(&self) {
+        // bind the item, so it gets dropped after the borrow ends
+        let _item = self.built.replace(None);
+    }
+
+    fn is_cached(&self, container: &Container) -> bool {
+        // without a clock nothing is cached, resolving reports the error
+        let now = match peek_clock(container) {
+            Ok(clock) => clock.now(),
+            Err(_) => return false,
+        };
+
+        match &*self.built.borrow() {
+            Some((_, built_at)) => now < *built_at + self.ttl,
+            None => false,
+        }
+    }
+}
+
+impl<T> fmt::Debug for TtlCache<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let built = if self.built.borrow().is_some() {
+            "built"
+        } else {
+            "not built"
+        };
+
+        write!(f, "TtlCache({:?}, {})", self.ttl, built)
+    }
+}
+
+fn clock(container: &Container) -> Result<Rc<dyn Clock>> {
+    use crate::Resolver;
+
+    if container.has::<Rc<dyn Clock>>() {
+        container.resolve::<Rc<dyn Clock>>()
+    } else {
+        Ok(Rc::new(SystemClock))
+    }
+}
+
+/// Like clock(), but without resolving anything, so observers don't see
+/// the clock when the cache is checked.
+fn peek_clock(container: &Container) -> Result<Rc<dyn Clock>> {
+    let type_id = TypeId::of::<Rc<dyn Clock>>();
+
+    if container.holds_item(type_id) {
+        container.get_shared::<Rc<dyn Clock>>(type_id)
+    } else {
+        clock(container)
+    }
+}
+
+impl Entry {
+    /// Entry for T, with a factory that caches the item for ttl.
+    ///
+    /// Forks get their own cache, and a copy of the builder.
+    #[track_caller]
+    pub(super) fn ttl<T, F>(ttl: Duration, builder: F) -> Entry
+    where
+        F: (FnMut(&Container) -> T) + Clone + 'static,
+        T: Clone + 'static,
+    {
+        let forker = move || {
+            let cache = Rc::new(TtlCache {
+                ttl,
+                built: RefCell::new(None),
+            });
+            let expiry: Rc<dyn Expiry> = cache.clone();
+            let mut builder = builder.clone();
+
+            // a factory with no arguments, so getting the clock can fail
+            let factory = move |container: &Container, ()| {
+                let now = clock(container)?.now();
+
+                if let Some(item) = cache.get(now) {
+                    return Ok(item);
+                }
+
+                let item = builder(container);
+                let _expired = cache.built.replace(Some((item.clone(), now)));
+
+                Ok(item)
+            };
+
+            (Resolver::factory_with::<(), T, _>(factory), Some(expiry))
+        };
+
+        Entry {
+            kind: ResolverKind::Ttl,
+            ..Entry::forkable::<T>(Forker(Rc::new(forker)))
+        }
+    }
+}
+
+impl Container {
+    /// Forgets the item of a builder with a time to live, so it's built
+    /// again on the next resolve, even if it did not expire yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::cell::Cell;
+    /// # use std::rc::Rc;
+    /// # use std::time::Duration;
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// # fn main() -> std::result::Result<(), String> {
+    /// #
+    /// let counter = Rc::new(Cell::new(0));
+    ///
+    /// let mut builder = ContainerBuilder::new();
+    /// builder.register_builder_ttl::<u32, _>(Duration::from_secs(3600), move |_| {
+    ///     counter.set(counter.get() + 1);
+    ///     counter.get()
+    /// })?;
+    ///
+    /// let container = builder.build();
+    /// assert_eq!(1, container.resolve::<u32>()?);
+    ///
+    /// container.invalidate::<u32>()?;
+    /// assert_eq!(2, container.resolve::<u32>()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn invalidate<T: 'static>(&self) -> Result<()> {
+        // every binding from merged builders is invalidated too
+        let expiries: Vec<Rc<dyn Expiry>> = match self.entries.borrow().get(&TypeId::of::<T>()) {
+            Some(entry) => entry
+                .expiry
+                .iter()
+                .cloned()
+                .chain(
+                    entry
+                        .bindings
+                        .iter()
+                        .filter_map(|binding| binding.borrow().expiry.clone()),
+                )
+                .collect(),
+            None => {
+                return Err(Error::not_registered(format!(
+                    "Type not registered: {}",
+                    type_name::<T>()
+                )))
+            }
+        };
+
+        if expiries.is_empty() {
+            return Err(format!(
+                "Type {} is not registered with a time to live",
+                type_name::<T>()
+            )
+            .into());
+        }
+
+        for expiry in expiries {
+            expiry.invalidate();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::{Clock, ManualClock};
+    use crate::{Container, ContainerBuilder, Resolver, ResolverKind};
+
+    fn container(clock: &Rc<ManualClock>) -> Container {
+        let builds = Cell::new(0);
+
+        let mut builder = ContainerBuilder::new();
+        builder.register::<Rc<dyn Clock>>(clock.clone()).unwrap();
+        builder
+            .register_builder_ttl::<u32, _>(Duration::from_secs(10), move |_| {
+                builds.set(builds.get() + 1);
+                builds.get()
+            })
+            .unwrap();
+
+        builder.build()
+    }
+
+    #[test]
+    fn items_are_built_again_after_they_expire() {
+        let clock = Rc::new(ManualClock::new());
+        let container = container(&clock);
+
+        assert_eq!(1, container.resolve::<u32>().unwrap());
+
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(1, container.resolve::<u32>().unwrap());
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(2, container.resolve::<u32>().unwrap());
+        assert_eq!(2, container.resolve::<u32>().unwrap());
+
+        let registration = &container.registrations()[1];
+        assert_eq!(ResolverKind::Ttl, registration.kind());
+    }
+
+    #[test]
+    fn invalidated_items_are_built_again() {
+        let clock = Rc::new(ManualClock::new());
+        let container = container(&clock);
+
+        assert_eq!(1, container.resolve::<u32>().unwrap());
+
+        container.invalidate::<u32>().unwrap();
+        assert_eq!(2, container.resolve::<u32>().unwrap());
+
+        assert!(container.invalidate::<Rc<dyn Clock>>().is_err());
+        assert!(container
+            .invalidate::<String>()
+            .unwrap_err()
+            .is_not_registered());
+    }
+
+    #[test]
+    fn clock_errors_are_returned() {
+        let mut builder = ContainerBuilder::new();
+        builder
+            .register_builder::<Rc<dyn Clock>, _>(|_| panic!("no clock"))
+            .unwrap();
+        builder
+            .register_builder_ttl::<u32, _>(Duration::from_secs(10), |_| 1)
+            .unwrap();
+
+        let container = builder.build();
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| container.resolve::<u32>()));
+
+        assert!(panicked.is_err());
+        assert!(container.resolve::<u32>().is_err());
+    }
+}
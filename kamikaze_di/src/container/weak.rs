@@ -0,0 +1,306 @@
+use std::any::{type_name, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::{Rc, Weak};
+
+use super::injector::{InjectAsRc, Injectable};
+use super::lazy::Lazy;
+use super::{Container, ResolverKind};
+use crate::{Error, Result};
+
+/// Weak<T>s handed out while their Rc<T> is being built.
+///
+/// Once a Weak<T> is injected while Rc<T> is being built, Rc<T> is
+/// built again with Rc::new_cyclic(), which can hand out Weak<T> before
+/// T exists. Later builds of Rc<T> go straight to Rc::new_cyclic().
+#[derive(Debug, Default)]
+pub(crate) struct WeakSlots {
+    cyclic: RefCell<HashSet<TypeId>>,
+    building: RefCell<HashMap<TypeId, Box<dyn Any>>>,
+}
+
+impl WeakSlots {
+    fn is_cyclic(&self, type_id: TypeId) -> bool {
+        self.cyclic.borrow().contains(&type_id)
+    }
+
+    fn building<T: 'static>(&self, type_id: TypeId) -> Option<Weak<T>> {
+        self.building
+            .borrow()
+            .get(&type_id)
+            .and_then(|weak| weak.downcast_ref::<Weak<T>>())
+            .cloned()
+    }
+
+    /// Hands out weak until the guard is dropped.
+    fn fill<T: 'static>(&self, type_id: TypeId, weak: Weak<T>) -> Filled<'_> {
+        let previous = self.building.borrow_mut().insert(type_id, Box::new(weak));
+
+        Filled {
+            slots: self,
+            type_id,
+            previous,
+        }
+    }
+}
+
+struct Filled<'a> {
+    slots: &'a WeakSlots,
+    type_id: TypeId,
+    previous: Option<Box<dyn Any>>,
+}
+
+impl Drop for Filled<'_> {
+    fn drop(&mut self) {
+        let mut building = self.slots.building.borrow_mut();
+
+        match self.previous.take() {
+            Some(previous) => building.insert(self.type_id, previous),
+            None => building.remove(&self.type_id),
+        };
+    }
+}
+
+/// Carries an error out of Rc::new_cyclic().
+struct Unbuilt;
+
+impl Container {
+    /// Weak<T> for an InjectAsRc T, building Rc<T> if needed.
+    ///
+    /// While Rc<T> is being built, the Weak<T> of the Rc<T> being built
+    /// with Rc::new_cyclic() is handed out. The first time that happens,
+    /// this fails, and Rc<T> gets built again with Rc::new_cyclic(), see
+    /// build_rc().
+    pub(crate) fn inject_weak<T: InjectAsRc + 'static>(&self) -> Result<Weak<T>> {
+        let type_id = TypeId::of::<Rc<T>>();
+
+        if self.dependencies.is_resolving_type(type_id) {
+            if let Some(weak) = self.weak.building::<T>(type_id) {
+                return Ok(weak);
+            }
+
+            self.weak.cyclic.borrow_mut().insert(type_id);
+
+            return Err(unwired::<T>());
+        }
+
+        match self.get_resolver_kind(type_id) {
+            Some(_) => self.resolve_weak::<T>(),
+            None => Ok(Rc::downgrade(&Rc::<T>::inject(self)?)),
+        }
+    }
+
+    /// Rc<T>, built with Rc::new_cyclic() if a Weak<T> was injected while
+    /// building it.
+    ///
+    /// The first build is thrown away then, so resolve() runs again. If
+    /// Rc<T> can't be built, what was injected while building it is
+    /// dropped too. Errors are carried out of Rc::new_cyclic() by unwinding, so when
+    /// panics abort, Weak<T> can't be injected while Rc<T> is being built.
+    pub(crate) fn build_rc<T: 'static>(
+        &self,
+        resolve: fn(&Container) -> Result<T>,
+    ) -> Result<Rc<T>> {
+        let type_id = TypeId::of::<Rc<T>>();
+
+        if !self.weak.is_cyclic(type_id) {
+            let item = resolve(self).map(Rc::new);
+
+            if !cfg!(panic = "unwind") || !self.weak.is_cyclic(type_id) {
+                return item;
+            }
+        }
+
+        let injected = self.injected_types();
+        let mut error = None;
+        let built = panic::catch_unwind(AssertUnwindSafe(|| {
+            Rc::new_cyclic(|weak: &Weak<T>| {
+                let _filled = self.weak.fill(type_id, weak.clone());
+
+                match resolve(self) {
+                    Ok(item) => item,
+                    Err(unbuilt) => {
+                        error = Some(unbuilt);
+
+                        panic::resume_unwind(Box::new(Unbuilt))
+                    }
+                }
+            })
+        }));
+
+        match (built, error) {
+            (Ok(item), _) => Ok(item),
+            (Err(_), Some(error)) => {
+                // they hold a Weak<T> that can't be upgraded
+                self.entries.borrow_mut().retain(|type_id, entry| {
+                    entry.kind != ResolverKind::Injected || injected.contains(type_id)
+                });
+
+                Err(error)
+            }
+            (Err(payload), None) => panic::resume_unwind(payload),
+        }
+    }
+
+    fn injected_types(&self) -> HashSet<TypeId> {
+        let entries = self.entries.borrow();
+        let injected = entries
+            .iter()
+            .filter(|(_, entry)| entry.kind == ResolverKind::Injected);
+
+        injected.map(|(type_id, _)| *type_id).collect()
+    }
+
+    /// Weak<T> for a registered Rc<T>.
+    pub(crate) fn resolve_weak<T: ?Sized + 'static>(&self) -> Result<Weak<T>> {
+        match self.get_resolver_kind(TypeId::of::<Rc<T>>()) {
+            Some(ResolverKind::Factory)
+            | Some(ResolverKind::PerGraph)
+            | Some(ResolverKind::Ttl)
+            | Some(ResolverKind::Keyed) => Err(format!(
+                "{} is registered as a factory, {} would be dropped right away",
+                type_name::<Rc<T>>(),
+                type_name::<T>()
+            )
+            .into()),
+            _ => self.get::<Rc<T>>().map(|item| Rc::downgrade(&item)),
+        }
+    }
+}
+
+fn unwired<T>() -> Error {
+    if cfg!(panic = "unwind") {
+        return format!(
+            "{} was injected while {} is being built, it gets built again to wire it",
+            type_name::<Weak<T>>(),
+            type_name::<Rc<T>>()
+        )
+        .into();
+    }
+
+    format!(
+        "{} was injected while {} is being built, inject {} when panics abort",
+        type_name::<Weak<T>>(),
+        type_name::<Rc<T>>(),
+        type_name::<Lazy<Weak<T>>>()
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::{Rc, Weak};
+
+    use crate::{Container, ContainerBuilder, InjectAsRc, Injector, Resolver, Result};
+
+    struct Parent {
+        child: Rc<Child>,
+    }
+
+    struct Child {
+        parent: Weak<Parent>,
+    }
+
+    impl InjectAsRc for Parent {
+        fn resolve(container: &Container) -> Result<Self> {
+            let child = container.inject()?;
+
+            let built: Rc<Cell<u32>> = container.resolve()?;
+            built.set(built.get() + 1);
+
+            Ok(Parent { child })
+        }
+    }
+
+    impl InjectAsRc for Child {
+        fn resolve(container: &Container) -> Result<Self> {
+            Ok(Child {
+                parent: container.inject()?,
+            })
+        }
+    }
+
+    fn container() -> (Container, Rc<Cell<u32>>) {
+        let built = Rc::new(Cell::new(0));
+
+        let mut builder = ContainerBuilder::new();
+        builder.register::<Rc<Cell<u32>>>(built.clone()).unwrap();
+
+        (builder.build(), built)
+    }
+
+    #[test]
+    fn wires_cycles_starting_with_the_parent() {
+        let (container, built) = container();
+
+        let parent: Rc<Parent> = container.inject().unwrap();
+        let child: Rc<Child> = container.inject().unwrap();
+
+        assert!(Rc::ptr_eq(&parent.child, &child));
+        assert!(Rc::ptr_eq(&parent, &child.parent.upgrade().unwrap()));
+        assert_eq!(1, built.get());
+    }
+
+    #[test]
+    fn wires_cycles_starting_with_the_child() {
+        let (container, built) = container();
+
+        let child: Rc<Child> = container.inject().unwrap();
+        let parent: Rc<Parent> = container.inject().unwrap();
+
+        assert!(Rc::ptr_eq(&child, &parent.child));
+        assert!(Rc::ptr_eq(&parent, &child.parent.upgrade().unwrap()));
+        assert_eq!(1, built.get());
+    }
+
+    #[test]
+    fn failed_cycles_are_errors() {
+        // Rc<Cell<u32>> is not registered, so Parent can't be built
+        let container = ContainerBuilder::new().build();
+
+        let parent: Result<Rc<Parent>> = container.inject();
+        let child: Result<Rc<Child>> = container.inject();
+
+        assert!(parent.is_err());
+        assert!(child.is_err());
+        assert!(!container.has::<Rc<Parent>>());
+    }
+
+    #[test]
+    fn registered_rcs_can_be_weak() {
+        struct Config;
+        impl InjectAsRc for Config {
+            fn resolve(_: &Container) -> Result<Self> {
+                Ok(Config)
+            }
+        }
+
+        let container = ContainerBuilder::new().build();
+        container.register_shared(Config).unwrap();
+
+        let weak: Weak<Config> = container.inject().unwrap();
+
+        assert!(weak.upgrade().is_some());
+    }
+
+    #[test]
+    fn weak_factories_are_errors() {
+        struct Session;
+        impl InjectAsRc for Session {
+            fn resolve(_: &Container) -> Result<Self> {
+                Ok(Session)
+            }
+        }
+
+        let container = ContainerBuilder::new().build();
+        container
+            .register_factory::<Rc<Session>, _>(|_| Rc::new(Session))
+            .unwrap();
+
+        let weak: Result<Weak<Session>> = container.inject();
+
+        assert!(weak.is_err());
+    }
+}
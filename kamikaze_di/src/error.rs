@@ -2,18 +2,58 @@
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct Error {
     message: String,
+    not_registered: bool,
+}
+
+impl Error {
+    /// Error for a type the container can't resolve because it,
+    /// or one of its dependencies, was never registered.
+    pub(crate) fn not_registered(message: String) -> Error {
+        Error {
+            message,
+            not_registered: true,
+        }
+    }
+
+    /// Same kind of error, with a different message.
+    pub(crate) fn with_message(&self, message: String) -> Error {
+        Error {
+            message,
+            not_registered: self.not_registered,
+        }
+    }
+
+    /// True if resolving failed because something was not registered,
+    /// as opposed to failing while creating the item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kamikaze_di::{ContainerBuilder, Resolver};
+    /// #
+    /// let container = ContainerBuilder::new().build();
+    ///
+    /// let error = container.resolve::<u32>().err().unwrap();
+    ///
+    /// assert!(error.is_not_registered());
+    /// ```
+    pub fn is_not_registered(&self) -> bool {
+        self.not_registered
+    }
 }
 
 impl From<String> for Error {
     fn from(message: String) -> Error {
-        Error { message }
+        Error {
+            message,
+            not_registered: false,
+        }
     }
 }
 
 impl From<&str> for Error {
     fn from(message: &str) -> Error {
-        let message = message.to_string();
-        Error { message }
+        message.to_string().into()
     }
 }
 
@@ -46,7 +46,6 @@
 //! # }
 //! ```
 #![doc(html_root_url = "https://docs.rs/kamikaze_di/0.1.0")]
-#![feature(specialization)]
 #![deny(
     missing_docs,
     missing_debug_implementations,
@@ -58,17 +57,96 @@
     unused_qualifications
 )]
 
-#[macro_use]
-extern crate log;
-
+#[cfg(feature = "config")]
+mod config;
 mod container;
 mod error;
 
+#[cfg(feature = "config")]
+pub use config::{
+    ConfigFile, ConfigSection, ConfigSource, EnvVars, InMemory, LayeredConfig, Settings,
+};
+pub use container::assisted::AssistedFactory;
+pub use container::blueprint::Blueprint;
 pub use container::builder::ContainerBuilder;
-pub use container::injector::{Inject, InjectAsRc, Injector};
+pub use container::graph::{Dependency, DependencyGraph, GraphEdge, GraphNode};
+pub use container::injector::{Inject, InjectAsArc, InjectAsRc, Injectable, Injector};
+pub use container::lazy::Lazy;
+pub use container::merge::{Conflict, ConflictPolicy};
+pub use container::module::Module;
+pub use container::observer::{CacheStatus, ResolutionEvent, ResolutionObserver};
+pub use container::per_thread::PerThread;
+pub use container::pool::{Pool, PoolStats, Pooled};
+pub use container::profile::SkippedRegistration;
+pub use container::registration::Registration;
 pub use container::resolver::Resolver;
-pub use container::Container;
+pub use container::shared_ref::SharedRef;
+pub use container::ttl::{Clock, ManualClock, SystemClock};
+pub use container::{Container, ResolverKind};
 pub use error::Error;
 
 /// Result type
 pub type Result<T> = std::result::Result<T, Error>;
+
+/// Used by kamikaze_di_derive, not public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::container::injector::{
+        field_error, FieldInjector, InjectField, ResolveField, ResolveLazyField,
+        ResolveOptionalField, ResolveWeakField,
+    };
+}
+
+/// Injects any type, registered or [Injectable](trait.Injectable.html).
+///
+/// [Injector::inject()](trait.Injector.html#tymethod.inject) only works
+/// for injectable types. This also resolves registered types that are not,
+/// like types from other crates or `Rc<dyn Trait>`, the same way fields of
+/// derived types are.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate kamikaze_di;
+/// # use std::rc::Rc;
+/// # use kamikaze_di::ContainerBuilder;
+/// #
+/// # fn main() -> std::result::Result<(), String> {
+/// #
+/// trait Voice {
+///     fn say(&self) -> String;
+/// }
+///
+/// struct Quiet;
+///
+/// impl Voice for Quiet {
+///     fn say(&self) -> String {
+///         "...".to_string()
+///     }
+/// }
+///
+/// let mut builder = ContainerBuilder::new();
+/// builder.register::<Rc<dyn Voice>>(Rc::new(Quiet))?;
+///
+/// let container = builder.build();
+/// let voice = inject!(container, Rc<dyn Voice>)?;
+///
+/// assert_eq!("...", voice.say());
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! inject {
+    ($container:expr, $ty:ty) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::{
+            InjectField, ResolveField, ResolveLazyField, ResolveOptionalField, ResolveWeakField,
+        };
+
+        let result: $crate::Result<$ty> =
+            (&&&$crate::__private::FieldInjector::<$ty>::new()).inject_field(&$container);
+
+        result
+    }};
+}
@@ -7,11 +7,11 @@ extern crate quote;
 extern crate syn;
 
 use proc_macro::TokenStream;
-use quote::{quote, quote_spanned};
+use quote::{quote, quote_spanned, ToTokens};
 use syn::spanned::Spanned;
 use syn::{
-    parse_macro_input, parse_str, Data, DeriveInput, Fields, FieldsNamed, FieldsUnnamed, Ident,
-    Path,
+    parse_macro_input, parse_str, Data, DeriveInput, Error, Field, Fields, FieldsNamed,
+    FieldsUnnamed, Ident, Meta, NestedMeta, Path,
 };
 
 #[proc_macro_derive(Inject)]
@@ -24,21 +24,56 @@ pub fn derive_resolve_to_rc(input: TokenStream) -> TokenStream {
     derive_code(input, "kamikaze_di::InjectAsRc")
 }
 
+#[proc_macro_derive(InjectAsArc)]
+pub fn derive_resolve_to_arc(input: TokenStream) -> TokenStream {
+    derive_code(input, "kamikaze_di::InjectAsArc")
+}
+
+/// Fields marked with `#[inject(arg)]` are the arguments of the factory,
+/// all other fields get injected.
+#[proc_macro_derive(AssistedFactory, attributes(inject))]
+pub fn derive_assisted_factory(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let name = input.ident;
+    let fields: Vec<Field> = match input.data {
+        Data::Struct(structure) => structure.fields.iter().cloned().collect(),
+        Data::Enum(enumeration) => {
+            return not_a_struct(enumeration.enum_token, "AssistedFactory");
+        }
+        Data::Union(union) => return not_a_struct(union.union_token, "AssistedFactory"),
+    };
+
+    derive_for_assisted(name, fields)
+}
+
+fn not_a_struct<T: ToTokens>(tokens: T, trait_name: &str) -> TokenStream {
+    let message = format!("{} can only be derived for structs", trait_name);
+
+    TokenStream::from(Error::new_spanned(tokens, message).to_compile_error())
+}
+
 fn derive_code(input: TokenStream, trait_path: &str) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     let name = input.ident;
     let resolve_type = parse_str::<Path>(trait_path).unwrap();
 
-    if let Data::Struct(structure) = input.data {
-        return match structure.fields {
+    let trait_name = trait_path.trim_start_matches("kamikaze_di::");
+
+    match input.data {
+        Data::Struct(structure) => match structure.fields {
             Fields::Named(fields) => derive_for_named(name, fields, resolve_type),
             Fields::Unnamed(fields) => derive_for_unnamed(name, fields, resolve_type),
-            _ => unimplemented!(),
-        };
-    };
+            Fields::Unit => {
+                let message = format!("{} can't be derived for unit structs", trait_name);
 
-    unimplemented!()
+                TokenStream::from(Error::new_spanned(name, message).to_compile_error())
+            }
+        },
+        Data::Enum(enumeration) => not_a_struct(enumeration.enum_token, trait_name),
+        Data::Union(union) => not_a_struct(union.union_token, trait_name),
+    }
 }
 
 fn derive_for_named(name: Ident, fields: FieldsNamed, resolve_type: Path) -> TokenStream {
@@ -58,13 +93,15 @@ fn derive_for_named(name: Ident, fields: FieldsNamed, resolve_type: Path) -> Tok
             quote! {}
         };
 
+        let inject = inject_field(field);
+
         quote_spanned! {field.span()=>
             #name: {
                 #log_debug
-                kamikaze_di::Injector::inject(container).map_err(|s| {
+                #inject.map_err(|error| {
                     #log_warning
 
-                    format!("could not resolve {}::{}: {}", #quoted_name, #ty, s)
+                    kamikaze_di::__private::field_error(error, #quoted_name, #ty)
                 })?
             },
         }
@@ -76,6 +113,8 @@ fn derive_for_named(name: Ident, fields: FieldsNamed, resolve_type: Path) -> Tok
         quote! {}
     };
 
+    let dependencies = fields.named.iter().map(dependency);
+
     let quote = quote! {
         impl #resolve_type for #name {
             fn resolve(container: &kamikaze_di::Container) -> kamikaze_di::Result<Self> {
@@ -85,6 +124,10 @@ fn derive_for_named(name: Ident, fields: FieldsNamed, resolve_type: Path) -> Tok
                     #(#resolve_fields)*
                 })
             }
+
+            fn dependencies() -> Vec<kamikaze_di::Dependency> {
+                vec![#(#dependencies),*]
+            }
         }
     };
 
@@ -107,14 +150,16 @@ fn derive_for_unnamed(name: Ident, fields: FieldsUnnamed, resolve_type: Path) ->
             quote! {}
         };
 
+        let inject = inject_field(field);
+
         quote_spanned! {field.span()=>
             {
                 #log_debug
 
-                kamikaze_di::Injector::inject(container).map_err(|s| {
+                #inject.map_err(|error| {
                     #log_warning
 
-                    format!("could not resolve {}::{}: {}", #quoted_name, #ty, s)
+                    kamikaze_di::__private::field_error(error, #quoted_name, #ty)
                 })?
             },
         }
@@ -126,6 +171,8 @@ fn derive_for_unnamed(name: Ident, fields: FieldsUnnamed, resolve_type: Path) ->
         quote! {}
     };
 
+    let dependencies = fields.unnamed.iter().map(dependency);
+
     TokenStream::from(quote! {
         impl #resolve_type for #name {
             fn resolve(container: &kamikaze_di::Container) -> kamikaze_di::Result<Self> {
@@ -135,6 +182,161 @@ fn derive_for_unnamed(name: Ident, fields: FieldsUnnamed, resolve_type: Path) ->
                     #(#resolve_fields)*
                 ))
             }
+
+            fn dependencies() -> Vec<kamikaze_di::Dependency> {
+                vec![#(#dependencies),*]
+            }
+        }
+    })
+}
+
+fn derive_for_assisted(name: Ident, fields: Vec<Field>) -> TokenStream {
+    let quoted_name = quote!(#name).to_string();
+    let named = fields.iter().any(|field| field.ident.is_some());
+
+    let mut arg_names = vec![];
+    let mut arg_types = vec![];
+    let mut dependencies = vec![];
+
+    let mut args = vec![];
+    for field in &fields {
+        match is_arg(field) {
+            Ok(arg) => args.push(arg),
+            Err(error) => return TokenStream::from(error.to_compile_error()),
+        }
+    }
+
+    let values: Vec<_> = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let value = if args[index] {
+                let arg_name = match &field.ident {
+                    Some(ident) => ident.clone(),
+                    None => parse_str::<Ident>(&format!("arg_{}", index)).unwrap(),
+                };
+                let value = quote!(#arg_name);
+
+                arg_names.push(arg_name);
+                arg_types.push(field.ty.clone());
+
+                value
+            } else {
+                let ty = quote!(#field).to_string();
+                let inject = inject_field(field);
+                let value = quote_spanned! {field.span()=>
+                    #inject.map_err(|error| {
+                        kamikaze_di::__private::field_error(error, #quoted_name, #ty)
+                    })?
+                };
+
+                dependencies.push(dependency(field));
+
+                if cfg!(feature = "logging") {
+                    quote! {
+                        {
+                            debug!("resolving {}::{}", #quoted_name, #ty);
+
+                            #value
+                        }
+                    }
+                } else {
+                    value
+                }
+            };
+
+            match &field.ident {
+                Some(ident) => quote!(#ident: #value),
+                None => value,
+            }
+        })
+        .collect();
+
+    let (args_type, args_pattern) = if arg_types.len() == 1 {
+        let arg_type = &arg_types[0];
+        let arg_name = &arg_names[0];
+
+        (quote!(#arg_type), quote!(#arg_name))
+    } else {
+        (quote!((#(#arg_types),*)), quote!((#(#arg_names),*)))
+    };
+
+    let item = if named {
+        quote!(#name { #(#values),* })
+    } else {
+        quote!(#name ( #(#values),* ))
+    };
+
+    TokenStream::from(quote! {
+        impl kamikaze_di::AssistedFactory for #name {
+            type Args = #args_type;
+
+            fn create(
+                container: &kamikaze_di::Container,
+                args: Self::Args,
+            ) -> kamikaze_di::Result<Self> {
+                let #args_pattern = args;
+
+                Ok(#item)
+            }
+
+            fn dependencies() -> Vec<kamikaze_di::Dependency> {
+                vec![#(#dependencies),*]
+            }
         }
     })
 }
+
+/// True for fields marked with `#[inject(arg)]`, an error for any other
+/// inject attribute.
+fn is_arg(field: &Field) -> Result<bool, Error> {
+    let mut arg = false;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("inject"))
+    {
+        let words: Vec<NestedMeta> = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list.nested.into_iter().collect(),
+            _ => vec![],
+        };
+
+        match words.as_slice() {
+            [NestedMeta::Meta(Meta::Word(word))] if word == "arg" => arg = true,
+            _ => {
+                return Err(Error::new_spanned(
+                    attr,
+                    "unknown inject attribute, only #[inject(arg)] is supported",
+                ))
+            }
+        }
+    }
+
+    Ok(arg)
+}
+
+/// Injects fields that implement Injectable, resolves all others.
+/// Optional fields that can't be resolved are None.
+///
+/// See kamikaze_di::__private::FieldInjector for how this works.
+fn inject_field(field: &Field) -> impl ToTokens {
+    let ty = &field.ty;
+
+    quote! {
+        {
+            #[allow(unused_imports)]
+            use kamikaze_di::__private::{
+                InjectField, ResolveField, ResolveLazyField, ResolveOptionalField, ResolveWeakField,
+            };
+
+            (&&&kamikaze_di::__private::FieldInjector::<#ty>::new()).inject_field(container)
+        }
+    }
+}
+
+fn dependency(field: &Field) -> impl ToTokens {
+    let ty = &field.ty;
+
+    quote! { kamikaze_di::Dependency::of::<#ty>() }
+}
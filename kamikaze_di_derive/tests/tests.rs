@@ -1,9 +1,12 @@
+#![allow(dead_code)]
+
 #[macro_use]
 extern crate kamikaze_di_derive;
 extern crate kamikaze_di;
 
-use kamikaze_di::{ContainerBuilder, Injector, Result};
+use kamikaze_di::{ContainerBuilder, Injector, Lazy, Result};
 use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Inject, Clone)]
 struct X {
@@ -20,6 +23,11 @@ struct Z {
     _x: X,
 }
 
+#[derive(InjectAsArc)]
+struct W {
+    _x: X,
+}
+
 #[test]
 fn test_derive() {
     let mut builder = ContainerBuilder::new();
@@ -43,3 +51,204 @@ fn test_derive_to_rc() {
 
     assert!(z.is_ok());
 }
+
+#[test]
+fn test_derive_to_arc() {
+    let mut builder = ContainerBuilder::new();
+    builder.register::<usize>(42).unwrap();
+
+    let container = builder.build();
+
+    let w1: Arc<W> = container.inject().unwrap();
+    let w2: Arc<W> = container.inject().unwrap();
+
+    assert!(Arc::ptr_eq(&w1, &w2));
+}
+
+#[test]
+fn test_derive_declares_dependencies() {
+    use kamikaze_di::{Dependency, Inject, InjectAsRc};
+
+    assert_eq!(
+        vec![Dependency::of::<usize>()],
+        <X as Inject>::dependencies()
+    );
+    assert_eq!(
+        vec![Dependency::of::<X>()],
+        <Z as InjectAsRc>::dependencies()
+    );
+}
+
+trait Greeter {
+    fn greet(&self) -> String;
+}
+
+struct English;
+
+impl Greeter for English {
+    fn greet(&self) -> String {
+        "hello".to_string()
+    }
+}
+
+#[derive(Clone)]
+struct Settings {
+    name: String,
+}
+
+#[derive(Inject, Clone)]
+struct Greeting {
+    greeter: Rc<dyn Greeter>,
+    settings: Settings,
+}
+
+#[test]
+fn test_derive_resolves_registered_fields() {
+    let mut builder = ContainerBuilder::new();
+    builder
+        .register::<Rc<dyn Greeter>>(Rc::new(English))
+        .unwrap();
+    builder
+        .register(Settings {
+            name: "world".to_string(),
+        })
+        .unwrap();
+
+    let container = builder.build();
+
+    let greeting: Greeting = container.inject().unwrap();
+
+    assert_eq!("hello", greeting.greeter.greet());
+    assert_eq!("world", greeting.settings.name);
+}
+
+#[derive(Inject, Clone)]
+struct Reporter {
+    greeter: Option<Rc<dyn Greeter>>,
+    settings: Option<Settings>,
+    x: Option<X>,
+}
+
+#[test]
+fn test_derive_resolves_optional_fields() {
+    let container = ContainerBuilder::new().build();
+
+    let reporter: Reporter = container.inject().unwrap();
+
+    assert!(reporter.greeter.is_none());
+    assert!(reporter.settings.is_none());
+    assert!(reporter.x.is_none());
+
+    let mut builder = ContainerBuilder::new();
+    builder
+        .register::<Rc<dyn Greeter>>(Rc::new(English))
+        .unwrap();
+    builder.register::<usize>(42).unwrap();
+
+    let container = builder.build();
+
+    let reporter: Reporter = container.inject().unwrap();
+
+    assert!(reporter.greeter.is_some());
+    assert!(reporter.settings.is_none());
+    assert_eq!(42, reporter.x.unwrap().u);
+}
+
+#[derive(AssistedFactory)]
+struct UserSession {
+    settings: Settings,
+    #[inject(arg)]
+    user_id: u64,
+    x: X,
+    #[inject(arg)]
+    locale: &'static str,
+}
+
+#[derive(AssistedFactory)]
+struct Visit(#[inject(arg)] u64, X);
+
+#[test]
+fn test_derive_assisted_factory() {
+    use kamikaze_di::{AssistedFactory, Dependency};
+
+    let mut builder = ContainerBuilder::new();
+    builder.register::<usize>(42).unwrap();
+    builder
+        .register(Settings {
+            name: "world".to_string(),
+        })
+        .unwrap();
+    builder.register_assisted_factory::<UserSession>().unwrap();
+    builder.register_assisted_factory::<Visit>().unwrap();
+
+    let container = builder.build();
+
+    let session: UserSession = container.resolve_with((7u64, "en")).unwrap();
+
+    assert_eq!("world", session.settings.name);
+    assert_eq!(7, session.user_id);
+    assert_eq!(42, session.x.u);
+    assert_eq!("en", session.locale);
+
+    let visit: Visit = container.resolve_with(3u64).unwrap();
+    assert_eq!(3, visit.0);
+
+    assert_eq!(
+        vec![Dependency::of::<Settings>(), Dependency::of::<X>()],
+        <UserSession as AssistedFactory>::dependencies()
+    );
+}
+
+#[derive(InjectAsRc)]
+struct Node {
+    leaf: Rc<Leaf>,
+}
+
+#[derive(InjectAsRc)]
+struct Leaf {
+    node: std::rc::Weak<Node>,
+    greeter: std::rc::Weak<dyn Greeter>,
+}
+
+#[test]
+fn test_derive_resolves_weak_fields() {
+    let greeter: Rc<dyn Greeter> = Rc::new(English);
+
+    let mut builder = ContainerBuilder::new();
+    builder.register(greeter.clone()).unwrap();
+
+    let container = builder.build();
+
+    let leaf: Rc<Leaf> = container.inject().unwrap();
+    let node = leaf.node.upgrade().unwrap();
+
+    assert!(Rc::ptr_eq(&leaf, &node.leaf));
+    assert_eq!("hello", leaf.greeter.upgrade().unwrap().greet());
+}
+
+#[derive(InjectAsRc)]
+struct EventBus {
+    subscriber: Lazy<Rc<Subscriber>>,
+    greeter: Lazy<Rc<dyn Greeter>>,
+}
+
+#[derive(InjectAsRc)]
+struct Subscriber {
+    bus: Rc<EventBus>,
+}
+
+#[test]
+fn test_derive_resolves_lazy_fields() {
+    let greeter: Rc<dyn Greeter> = Rc::new(English);
+
+    let mut builder = ContainerBuilder::new();
+    builder.register(greeter.clone()).unwrap();
+
+    let container = builder.build();
+
+    let bus: Rc<EventBus> = container.inject().unwrap();
+    let subscriber = bus.subscriber.get().unwrap();
+
+    assert!(Rc::ptr_eq(&bus, &subscriber.bus));
+    assert_eq!("hello", bus.greeter.get().unwrap().greet());
+}